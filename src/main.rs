@@ -2,6 +2,7 @@
 extern crate lazy_static;
 extern crate async_std;
 extern crate async_trait;
+extern crate chrono;
 extern crate clap;
 extern crate colored;
 extern crate ctrlc;
@@ -9,37 +10,51 @@ extern crate fslock;
 extern crate juno;
 extern crate rand;
 extern crate semver;
+extern crate sha2;
+#[cfg(unix)]
+extern crate signal_hook;
 
 mod models;
 mod service;
 mod utils;
 
-use std::{env::current_dir, sync::Mutex};
+use std::{env::current_dir, fs, time::Duration};
 
 use async_std::task;
 
 use clap::{App, Arg};
 
+use models::{Backpressure, Listener};
 use utils::{
-	constants,
-	logger::{self, LogLevel},
+	config::Config,
+	constants, credentials,
+	logger::{self, file_logger::LogFile, filter::Filter, LogLevel},
+	policy::{Policy, RateLimits},
 };
 
-lazy_static! {
-	static ref CONNECTION_TYPE: Mutex<u8> = Mutex::new(0);
-}
-
-#[allow(clippy::collapsible_if)]
 #[async_std::main]
 async fn main() {
+	run().await;
+	// Logs are written in the background, so whatever is left has to be written before exiting
+	logger::flush();
+}
+
+async fn run() {
 	let args =
 		App::new(constants::APP_NAME)
 			.version(constants::APP_VERSION)
 			.author(constants::APP_AUTHORS)
 			.about(constants::APP_DESCRIPTION)
+			.arg(
+				Arg::with_name("config")
+					.short("c")
+					.long("config")
+					.takes_value(true)
+					.value_name("FILE")
+					.help("Reads settings from a TOML config file. Command line flags and JUNO_* environment variables take precedence over it"),
+			)
 			.arg(
 				Arg::with_name("socket-location")
-					.conflicts_with("port")
 					.short("s")
 					.long("socket-location")
 					.takes_value(true)
@@ -48,21 +63,169 @@ async fn main() {
 			)
 			.arg(
 				Arg::with_name("port")
-					.conflicts_with("socket-location")
 					.short("p")
 					.long("port")
 					.takes_value(true)
 					.value_name("PORT")
+					.validator(|port| {
+						port.parse::<u16>()
+							.map(|_| ())
+							.map_err(|_| format!("{} is not a valid port", port))
+					})
 					.help("Sets the port for the socket to listen to"),
 			)
 			.arg(
 				Arg::with_name("bind-addr")
-					.conflicts_with("socket-location")
 					.long("bind-addr")
 					.takes_value(true)
 					.value_name("BIND-ADDR")
 					.help("Sets the binding address for the socket to listen to"),
 			)
+			.arg(
+				Arg::with_name("listen")
+					.short("l")
+					.long("listen")
+					.takes_value(true)
+					.multiple(true)
+					.number_of_values(1)
+					.value_name("LISTENER")
+					.help("Listens on a unix socket (unix:<FILE>) or a TCP address (<HOST>:<PORT>), along with every other listener given. Can be given more than once. Eg: -l unix:./juno.sock -l [::1]:2203"),
+			)
+			.arg(
+				Arg::with_name("call-timeout")
+					.long("call-timeout")
+					.takes_value(true)
+					.value_name("MILLISECONDS")
+					.help("Sets the default time to wait for a function call's response. 0 waits forever"),
+			)
+			.arg(
+				Arg::with_name("grace-period")
+					.long("grace-period")
+					.takes_value(true)
+					.value_name("MILLISECONDS")
+					.help("Sets how long a disconnected module is kept around for it to resume its session. Defaults to 0, which disables resumption"),
+			)
+			.arg(
+				Arg::with_name("credentials")
+					.long("credentials")
+					.takes_value(true)
+					.value_name("FILE")
+					.help("Sets the credentials file, mapping moduleIds to the tokens they must register with"),
+			)
+			.arg(
+				Arg::with_name("max-auth-failures")
+					.long("max-auth-failures")
+					.takes_value(true)
+					.value_name("COUNT")
+					.help("Sets how many failed registrations a connection is allowed before it is closed. 0 never closes it"),
+			)
+			.arg(
+				Arg::with_name("policy")
+					.long("policy")
+					.takes_value(true)
+					.value_name("FILE")
+					.help("Sets the policy file, declaring which modules can call which functions and listen for which hooks. Changes are picked up without a restart"),
+			)
+			.arg(
+				Arg::with_name("call-rate")
+					.long("call-rate")
+					.takes_value(true)
+					.value_name("PER_SECOND")
+					.help("Sets how many function calls a module can make per second. 0 doesn't limit it"),
+			)
+			.arg(
+				Arg::with_name("trigger-rate")
+					.long("trigger-rate")
+					.takes_value(true)
+					.value_name("PER_SECOND")
+					.help("Sets how many hooks a module can trigger per second. 0 doesn't limit it"),
+			)
+			.arg(
+				Arg::with_name("byte-rate")
+					.long("byte-rate")
+					.takes_value(true)
+					.value_name("BYTES_PER_SECOND")
+					.help("Sets how many bytes a module can send per second. 0 doesn't limit it"),
+			)
+			.arg(
+				Arg::with_name("queue-limit")
+					.long("queue-limit")
+					.takes_value(true)
+					.value_name("MESSAGES")
+					.help("Sets how many messages can be waiting to be written to a connection. 0 doesn't limit it"),
+			)
+			.arg(
+				Arg::with_name("backpressure")
+					.long("backpressure")
+					.takes_value(true)
+					.possible_values(&[
+						constants::backpressure::BLOCK,
+						constants::backpressure::DROP_OLDEST,
+						constants::backpressure::DISCONNECT,
+					])
+					.help("Sets what happens when a connection's queue is full, for modules that don't choose for themselves"),
+			)
+			.arg(
+				Arg::with_name("heartbeat-interval")
+					.long("heartbeat-interval")
+					.takes_value(true)
+					.value_name("MILLISECONDS")
+					.help("Sets how often connections are pinged to check that they're alive. 0 doesn't ping them"),
+			)
+			.arg(
+				Arg::with_name("max-missed-heartbeats")
+					.long("max-missed-heartbeats")
+					.takes_value(true)
+					.value_name("COUNT")
+					.help("Sets how many pings in a row a connection can leave unanswered before it is closed"),
+			)
+			.arg(
+				Arg::with_name("metrics-addr")
+					.long("metrics-addr")
+					.takes_value(true)
+					.value_name("ADDRESS")
+					.help("Serves Prometheus metrics over HTTP at /metrics on the given address. Eg: 127.0.0.1:9100"),
+			)
+			.arg(
+				Arg::with_name("log-format")
+					.long("log-format")
+					.takes_value(true)
+					.possible_values(&[constants::log_formats::CONSOLE, constants::log_formats::JSON])
+					.help("Sets how logs are written. json writes every log as a line of JSON, with structured fields"),
+			)
+			.arg(
+				Arg::with_name("log-file")
+					.long("log-file")
+					.takes_value(true)
+					.value_name("FILE")
+					.help("Writes the logs to the given file instead of printing them"),
+			)
+			.arg(
+				Arg::with_name("log-max-size")
+					.long("log-max-size")
+					.takes_value(true)
+					.value_name("BYTES")
+					.help("Rotates the log file once it grows past this size. 0 doesn't rotate it by size"),
+			)
+			.arg(
+				Arg::with_name("log-rotate-daily")
+					.long("log-rotate-daily")
+					.help("Rotates the log file every day"),
+			)
+			.arg(
+				Arg::with_name("log-retention")
+					.long("log-retention")
+					.takes_value(true)
+					.value_name("COUNT")
+					.help("Sets how many rotated log files are kept"),
+			)
+			.arg(
+				Arg::with_name("log-filter")
+					.long("log-filter")
+					.takes_value(true)
+					.value_name("DIRECTIVES")
+					.help("Sets the level of logs about specific modules or hooks. Eg: info,module:billing=verbose,hook:users.*=debug"),
+			)
 			.arg(Arg::with_name("V").short("V").multiple(true).help(
 				"Sets the level of verbosity (max 3). Eg: -VVV for the highest logging level",
 			))
@@ -79,75 +242,237 @@ async fn main() {
 		return;
 	}
 
-	let mut default_socket_location = current_dir().unwrap();
-	default_socket_location.push(constants::DEFAULT_SOCKET_LOCATION);
-	let default_socket_location = default_socket_location.as_os_str().to_str().unwrap();
-
-	let mut port = String::from(args.value_of("bind-addr").unwrap_or("127.0.0.1"));
-	port.push(':');
-	port.push_str(args.value_of("port").unwrap_or("2203"));
-
-	let socket_location = String::from(
-		args.value_of("socket-location")
-			.unwrap_or(default_socket_location),
-	);
+	let config = match Config::load(&args) {
+		Ok(config) => config,
+		Err(error) => {
+			logger::error(&error);
+			return;
+		}
+	};
 
 	let verbosity = match args.occurrences_of("V") {
-		0 => LogLevel::Warn,
-		1 => LogLevel::Debug,
-		2 => LogLevel::Info,
+		0 => config
+			.get("log-level")
+			.and_then(LogLevel::parse)
+			.unwrap_or(LogLevel::Warn),
+		1 => LogLevel::Info,
+		2 => LogLevel::Debug,
 		_ => LogLevel::Verbose,
 	};
-	logger::set_verbosity(verbosity);
+	match Filter::parse(config.get("log-filter").unwrap_or(""), verbosity) {
+		Ok(filter) => logger::set_filter(filter),
+		Err(error) => {
+			logger::error(&error);
+			return;
+		}
+	}
+
+	let log_file = if let Some(path) = config.get("log-file") {
+		let max_size = config.get("log-max-size").unwrap_or("0").parse::<u64>();
+		let retention = config
+			.get("log-retention")
+			.map(|retention| retention.parse::<usize>())
+			.unwrap_or(Ok(constants::DEFAULT_LOG_RETENTION));
+		if max_size.is_err() {
+			logger::error("Log max size is not a valid number of bytes");
+			return;
+		}
+		if retention.is_err() {
+			logger::error("Log retention is not a valid number of files");
+			return;
+		}
+		Some(LogFile {
+			path: String::from(path),
+			max_size: max_size.unwrap(),
+			daily: config.is_enabled("log-rotate-daily"),
+			retention: retention.unwrap(),
+		})
+	} else {
+		None
+	};
+	let log_format = config
+		.get("log-format")
+		.unwrap_or(constants::log_formats::CONSOLE);
+	if let Err(err) = logger::set_output(log_format, log_file) {
+		logger::error!("Unable to open log file: {}", err);
+		return;
+	}
 
-	let mut connection_type = CONNECTION_TYPE.lock().unwrap();
+	if let Some(timeout) = config.get("call-timeout") {
+		if let Ok(timeout) = timeout.parse::<u64>() {
+			service::data_handler::set_function_call_timeout(timeout).await;
+		} else {
+			logger::error("Function call timeout is not a valid number of milliseconds");
+			return;
+		}
+	}
 
-	if cfg!(target_family = "windows") {
-		if args.value_of("socket-location").is_some() {
-			logger::error("Listening on unix sockets are not supported on windows");
+	if let Some(grace_period) = config.get("grace-period") {
+		if let Ok(grace_period) = grace_period.parse::<u64>() {
+			service::data_handler::set_disconnect_grace_period(grace_period).await;
+		} else {
+			logger::error("Grace period is not a valid number of milliseconds");
 			return;
+		}
+	}
+
+	if let Some(credentials_file) = config.get("credentials") {
+		match credentials::load(credentials_file) {
+			Ok(credentials) => {
+				logger::info!(
+					"Loaded {} credentials. Modules will have to authenticate to register",
+					credentials.len()
+				);
+				service::data_handler::set_credentials(credentials).await;
+			}
+			Err(error) => {
+				logger::error(&error);
+				return;
+			}
+		}
+	}
+
+	if let Some(max_failures) = config.get("max-auth-failures") {
+		if let Ok(max_failures) = max_failures.parse::<u32>() {
+			service::data_handler::set_max_authentication_failures(max_failures).await;
 		} else {
-			*connection_type = constants::connection_types::INET_SOCKET;
+			logger::error("Max authentication failures is not a valid number");
+			return;
 		}
-	} else {
-		if args.value_of("port").is_some() {
-			*connection_type = constants::connection_types::INET_SOCKET;
+	}
+
+	let mut rate_limits = RateLimits::default();
+	for (arg, limit) in [
+		("call-rate", &mut rate_limits.calls),
+		("trigger-rate", &mut rate_limits.triggers),
+		("byte-rate", &mut rate_limits.bytes),
+	] {
+		if let Some(value) = config.get(arg) {
+			if let Ok(value) = value.parse::<u64>() {
+				*limit = Some(value);
+			} else {
+				logger::error!("{} is not a valid number", arg);
+				return;
+			}
+		}
+	}
+	service::data_handler::set_default_rate_limits(rate_limits).await;
+
+	if let Some(queue_limit) = config.get("queue-limit") {
+		if let Ok(queue_limit) = queue_limit.parse::<usize>() {
+			service::data_handler::set_queue_limit(queue_limit).await;
 		} else {
-			*connection_type = constants::connection_types::UNIX_SOCKET;
+			logger::error("Queue limit is not a valid number of messages");
+			return;
+		}
+	}
+
+	if let Some(backpressure) = config.get("backpressure") {
+		if let Some(backpressure) = Backpressure::parse(backpressure) {
+			service::data_handler::set_default_backpressure(backpressure).await;
+		} else {
+			logger::error!("{} is not a valid backpressure policy", backpressure);
+			return;
 		}
 	}
 
-	if *connection_type == constants::connection_types::UNIX_SOCKET {
-		logger::info(&format!(
-			"Starting {} on socket location {}",
-			constants::APP_NAME,
-			socket_location,
-		));
+	if let Some(heartbeat_interval) = config.get("heartbeat-interval") {
+		if let Ok(heartbeat_interval) = heartbeat_interval.parse::<u64>() {
+			service::data_handler::set_heartbeat_interval(heartbeat_interval).await;
+		} else {
+			logger::error("Heartbeat interval is not a valid number of milliseconds");
+			return;
+		}
+	}
+
+	if let Some(max_missed) = config.get("max-missed-heartbeats") {
+		if let Ok(max_missed) = max_missed.parse::<u32>() {
+			service::data_handler::set_max_missed_heartbeats(max_missed).await;
+		} else {
+			logger::error("Max missed heartbeats is not a valid number");
+			return;
+		}
+	}
 
-		ctrlc::set_handler(move || task::block_on(on_exit()))
-			.expect("Unable to set Ctrl-C handler");
-		drop(connection_type);
-		if let Err(err) = service::start(&socket_location).await {
-			logger::error(&format!("Error creating socket: {}", err));
+	if let Some(policy_file) = config.get("policy") {
+		match Policy::load(policy_file) {
+			Ok(policy) => {
+				logger::info("Loaded policy. Function calls and hooks will be restricted");
+				service::data_handler::set_policy(policy).await;
+				task::spawn(watch_policy(String::from(policy_file)));
+			}
+			Err(error) => {
+				logger::error(&error);
+				return;
+			}
 		}
-	} else if *connection_type == constants::connection_types::INET_SOCKET {
-		logger::info(&format!(
-			"Starting {} on port {}",
-			constants::APP_NAME,
-			port,
-		));
+	}
 
-		ctrlc::set_handler(move || task::block_on(on_exit()))
-			.expect("Unable to set Ctrl-C handler");
-		drop(connection_type);
-		if let Err(err) = service::start(&port).await {
-			logger::error(&format!("Error opening socket: {}", err));
+	if let Some(metrics_address) = config.get("metrics-addr") {
+		task::spawn(service::metrics_server::listen(String::from(
+			metrics_address,
+		)));
+	}
+
+	let mut listeners = config.get_listeners().to_vec();
+	if listeners.is_empty() {
+		if cfg!(target_family = "windows") {
+			listeners.push(Listener::from_port(
+				constants::DEFAULT_BIND_ADDRESS,
+				constants::DEFAULT_PORT,
+			));
+		} else {
+			let mut default_socket_location = current_dir().unwrap();
+			default_socket_location.push(constants::DEFAULT_SOCKET_LOCATION);
+			listeners.push(Listener::Unix(String::from(
+				default_socket_location.as_os_str().to_str().unwrap(),
+			)));
 		}
 	}
+	if cfg!(target_family = "windows") &&
+		listeners
+			.iter()
+			.any(|listener| matches!(listener, Listener::Unix(_)))
+	{
+		logger::error("Listening on unix sockets are not supported on windows");
+		return;
+	}
+
+	for listener in listeners.iter() {
+		logger::info!("Starting {} on {}", constants::APP_NAME, listener);
+	}
+
+	ctrlc::set_handler(move || task::block_on(on_exit())).expect("Unable to set Ctrl-C handler");
+	if let Err(err) = service::start(&listeners).await {
+		logger::error!("Error opening socket: {}", err);
+	}
 }
 
-fn get_connection_type() -> u8 {
-	*CONNECTION_TYPE.lock().unwrap()
+// Reloads the policy whenever the policy file changes. If the new policy
+// can't be loaded, the previous one stays in effect
+async fn watch_policy(policy_file: String) {
+	let get_modified_time = || fs::metadata(&policy_file).and_then(|metadata| metadata.modified());
+	let mut last_modified = get_modified_time().ok();
+
+	loop {
+		task::sleep(Duration::from_millis(constants::POLICY_RELOAD_INTERVAL)).await;
+
+		let modified = get_modified_time().ok();
+		if modified == last_modified {
+			continue;
+		}
+		last_modified = modified;
+
+		match Policy::load(&policy_file) {
+			Ok(policy) => {
+				logger::info("Policy file changed. Reloaded policy");
+				service::data_handler::set_policy(policy).await;
+			}
+			Err(error) => {
+				logger::error!("{}. Keeping the previous policy", error);
+			}
+		}
+	}
 }
 
 async fn on_exit() {
@@ -1,5 +1,13 @@
-use crate::{constants, models::Module, service::data_handler};
-use juno::{models::Value, JunoModuleImpl};
+use crate::{
+	constants,
+	logger::{self, filter::Filter},
+	models::Module,
+	service::data_handler,
+};
+use juno::{
+	models::{Number, Value},
+	JunoModuleImpl,
+};
 
 use std::{collections::HashMap, sync::Arc};
 
@@ -119,6 +127,21 @@ pub(crate) async fn setup_juno_module(
 		.await
 		.unwrap();
 
+	module
+		.declare_function("getDependencyStatus", get_dependency_status)
+		.await
+		.unwrap();
+
+	module
+		.declare_function("getLogFilter", get_log_filter)
+		.await
+		.unwrap();
+
+	module
+		.declare_function("setLogFilter", set_log_filter)
+		.await
+		.unwrap();
+
 	module
 }
 
@@ -146,15 +169,72 @@ fn get_module_info(args: HashMap<String, Value>) -> Value {
 	}
 }
 
+fn get_dependency_status(args: HashMap<String, Value>) -> Value {
+	let module_id = args.get("moduleId");
+	if module_id.is_none() {
+		return Value::Null;
+	}
+	let module_id = module_id.unwrap().as_string();
+	if module_id.is_none() {
+		return Value::Null;
+	}
+	let module_id = module_id.unwrap();
+
+	if let Some(status) = task::block_on(data_handler::get_dependency_status(module_id)) {
+		Value::from(status)
+	} else {
+		Value::Null
+	}
+}
+
+fn get_log_filter(_: HashMap<String, Value>) -> Value {
+	Value::String(logger::get_filter().to_string())
+}
+
+// Replaces the log filter. Unless the new one sets the level of every
+// log, the level stays what it was. Returns the filter now in effect
+fn set_log_filter(args: HashMap<String, Value>) -> Value {
+	let filter = args.get("filter");
+	if filter.is_none() {
+		return Value::Null;
+	}
+	let filter = filter.unwrap().as_string();
+	if filter.is_none() {
+		return Value::Null;
+	}
+	let filter = filter.unwrap();
+
+	match Filter::parse(filter, logger::get_filter().get_default()) {
+		Ok(filter) => {
+			logger::info!("Log filter changed to '{}'", filter);
+			logger::set_filter(filter);
+			Value::String(logger::get_filter().to_string())
+		}
+		Err(error) => {
+			logger::warn(&error);
+			Value::Null
+		}
+	}
+}
+
 fn get_object_from_module(module: Module) -> Value {
+	let connection_ids = module.get_connection_uuids();
+	let queue_depths = module.get_queue_depths();
+	let dropped_messages = module.get_dropped_message_count();
+	let rate_limits = task::block_on(data_handler::get_rate_limit_usage(module.get_module_id()));
 	let Module {
 		module_id,
 		module_uuid,
 		registered,
 		version,
 		dependencies,
+		optional_dependencies,
 		declared_functions,
+		mut argument_schemas,
+		mut response_schemas,
 		registered_hooks,
+		replicable,
+		backpressure,
 		..
 	} = module;
 	Value::Object({
@@ -165,6 +245,38 @@ fn get_object_from_module(module: Module) -> Value {
 			String::from("connectionId"),
 			Value::String(format!("{}", module_uuid)),
 		);
+		map.insert(
+			String::from("replicas"),
+			Value::Array(
+				connection_ids
+					.into_iter()
+					.map(|connection_id| Value::String(format!("{}", connection_id)))
+					.collect(),
+			),
+		);
+		map.insert(String::from("replicable"), Value::Bool(replicable));
+		map.insert(
+			String::from("backpressure"),
+			Value::String(String::from(backpressure.get_name())),
+		);
+		map.insert(
+			String::from("queueDepths"),
+			Value::Object(
+				queue_depths
+					.into_iter()
+					.map(|(connection_id, depth)| {
+						(
+							format!("{}", connection_id),
+							Value::Number(Number::PosInt(depth as u64)),
+						)
+					})
+					.collect(),
+			),
+		);
+		map.insert(
+			String::from("droppedMessages"),
+			Value::Number(Number::PosInt(dropped_messages)),
+		);
 		map.insert(String::from("version"), Value::String(version.to_string()));
 		map.insert(
 			String::from("dependencies"),
@@ -175,7 +287,43 @@ fn get_object_from_module(module: Module) -> Value {
 					.collect(),
 			),
 		);
+		map.insert(
+			String::from("optionalDependencies"),
+			Value::Object(
+				optional_dependencies
+					.into_iter()
+					.map(|(key, value)| (key, Value::String(value.to_string())))
+					.collect(),
+			),
+		);
 		map.insert(String::from("registered"), Value::Bool(registered));
+		map.insert(String::from("rateLimits"), Value::from(rate_limits));
+		map.insert(
+			String::from("functionSchemas"),
+			Value::Object(
+				declared_functions
+					.iter()
+					.map(|function| {
+						let mut schemas = HashMap::new();
+						schemas.insert(
+							String::from("arguments"),
+							argument_schemas
+								.remove(function)
+								.map(Value::from)
+								.unwrap_or(Value::Null),
+						);
+						schemas.insert(
+							String::from("response"),
+							response_schemas
+								.remove(function)
+								.map(Value::from)
+								.unwrap_or(Value::Null),
+						);
+						(function.clone(), Value::Object(schemas))
+					})
+					.collect(),
+			),
+		);
 		map.insert(
 			String::from("declaredFunctions"),
 			Value::Array(declared_functions.into_iter().map(Value::String).collect()),
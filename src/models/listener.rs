@@ -0,0 +1,89 @@
+use crate::utils::constants;
+
+use std::fmt;
+
+// Where juno listens for connections. Every listener shares the same modules,
+// so a module connected to one of them can call modules connected to another
+#[derive(Clone, PartialEq)]
+pub enum Listener {
+	// The path of a unix socket
+	Unix(String),
+	// The address of a TCP socket. Eg: 127.0.0.1:2203 or [::1]:2203
+	Inet(String),
+}
+
+impl Listener {
+	// Listeners are written as "unix:<path>" or "<host>:<port>"
+	pub fn parse(text: &str) -> Result<Self, String> {
+		if let Some(socket_path) = text.strip_prefix(constants::UNIX_LISTENER_PREFIX) {
+			if socket_path.is_empty() {
+				return Err(format!("Invalid listener '{}': the path is empty", text));
+			}
+			return Ok(Listener::Unix(String::from(socket_path)));
+		}
+
+		let port = text.rsplit_once(':').map(|(_, port)| port.parse::<u16>());
+		if let Some(Ok(_)) = port {
+			Ok(Listener::Inet(String::from(text)))
+		} else {
+			Err(format!(
+				"Invalid listener '{}': expected unix:<path> or <host>:<port>",
+				text
+			))
+		}
+	}
+
+	// IPv6 addresses are put in brackets, to keep them apart from the port
+	pub fn from_port(bind_address: &str, port: &str) -> Self {
+		if bind_address.contains(':') && !bind_address.starts_with('[') {
+			Listener::Inet(format!("[{}]:{}", bind_address, port))
+		} else {
+			Listener::Inet(format!("{}:{}", bind_address, port))
+		}
+	}
+}
+
+impl fmt::Display for Listener {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			Listener::Unix(socket_path) => write!(f, "socket location {}", socket_path),
+			Listener::Inet(address) => write!(f, "address {}", address),
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn parses_listeners() {
+		assert!(
+			Listener::parse("unix:./juno.sock").unwrap() ==
+				Listener::Unix(String::from("./juno.sock"))
+		);
+		assert!(
+			Listener::parse("127.0.0.1:2203").unwrap() ==
+				Listener::Inet(String::from("127.0.0.1:2203"))
+		);
+		assert!(
+			Listener::parse("[::1]:2203").unwrap() == Listener::Inet(String::from("[::1]:2203"))
+		);
+
+		assert!(Listener::parse("unix:").is_err());
+		assert!(Listener::parse("./juno.sock").is_err());
+		assert!(Listener::parse("127.0.0.1").is_err());
+		assert!(Listener::parse("127.0.0.1:").is_err());
+		assert!(Listener::parse("127.0.0.1:65536").is_err());
+	}
+
+	#[test]
+	fn brackets_ipv6_addresses() {
+		assert!(
+			Listener::from_port("127.0.0.1", "2203") ==
+				Listener::Inet(String::from("127.0.0.1:2203"))
+		);
+		assert!(Listener::from_port("::1", "2203") == Listener::Inet(String::from("[::1]:2203")));
+		assert!(Listener::from_port("[::1]", "2203") == Listener::Inet(String::from("[::1]:2203")));
+	}
+}
@@ -1,6 +1,20 @@
 pub mod juno_module;
+pub mod listener;
 pub mod module;
 pub mod module_comm;
+pub mod outbound_queue;
+pub mod pending_calls;
+pub mod request_origin;
+pub mod suspended_connection;
+pub mod token_bucket;
+pub mod unmet_dependency;
 
+pub use listener::Listener;
 pub use module::Module;
 pub use module_comm::ModuleComm;
+pub use outbound_queue::{Backpressure, Delivery, OutboundReceiver, OutboundSender};
+pub use pending_calls::PendingCalls;
+pub use request_origin::RequestOrigin;
+pub use suspended_connection::SuspendedConnection;
+pub use token_bucket::TokenBucket;
+pub use unmet_dependency::UnmetDependency;
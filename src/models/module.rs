@@ -1,12 +1,14 @@
-use crate::utils::logger;
+use crate::{
+	models::{outbound_queue::SendError, Backpressure, Delivery, OutboundSender},
+	utils::{hook_pattern, logger},
+};
 
 use std::collections::HashMap;
 
 use async_std::sync::RwLock;
-use futures::channel::mpsc::UnboundedSender;
-use futures_util::sink::SinkExt;
 
 use semver::{Version, VersionReq};
+use serde_json::Value;
 
 lazy_static! {
 	pub(crate) static ref JUNO_MODULE: RwLock<Option<Module>> = RwLock::new(None);
@@ -20,11 +22,27 @@ pub struct Module {
 	pub(crate) module_id: String,
 	pub(crate) version: Version,
 	pub(crate) dependencies: HashMap<String, VersionReq>,
+	// Dependencies that don't block activation. Their version is only checked if they are present
+	pub(crate) optional_dependencies: HashMap<String, VersionReq>,
 	pub(crate) declared_functions: Vec<String>,
+	// JSON schemas of the arguments and the responses of the declared functions, if any
+	pub(crate) argument_schemas: HashMap<String, Value>,
+	pub(crate) response_schemas: HashMap<String, Value>,
 	// These are the (global) hooks that this particular module is listening for
 	pub(crate) registered_hooks: Vec<String>,
+	// Whether other connections are allowed to register as replicas of this module
+	pub(crate) replicable: bool,
+	// What happens when one of the connections of this module isn't reading its data fast enough
+	pub(crate) backpressure: Backpressure,
 
-	module_sender: UnboundedSender<String>,
+	// Every connection serving this module, along with its sender. The first one is the module_uuid
+	module_connections: Vec<(u128, OutboundSender)>,
+	// Connections of a previous version of this module that was upgraded. They don't get any new
+	// function calls or hooks, and are closed once they have responded to their pending calls
+	draining_connections: Vec<u128>,
+	// Connections that were lost, and are waiting to be resumed. What is sent to them is queued
+	// until they are, so they are only picked when the module has no other connection
+	suspended_connections: Vec<u128>,
 }
 
 #[allow(dead_code)]
@@ -33,7 +51,7 @@ impl Module {
 		module_uuid: u128,
 		module_id: String,
 		version: Version,
-		module_sender: UnboundedSender<String>,
+		module_sender: OutboundSender,
 	) -> Self {
 		Module {
 			registered: false,
@@ -41,10 +59,17 @@ impl Module {
 			module_id,
 			version,
 			dependencies: HashMap::new(),
+			optional_dependencies: HashMap::new(),
 			declared_functions: vec![],
+			argument_schemas: HashMap::new(),
+			response_schemas: HashMap::new(),
 			registered_hooks: vec![],
+			replicable: false,
+			backpressure: module_sender.get_backpressure(),
 
-			module_sender,
+			module_connections: vec![(module_uuid, module_sender)],
+			draining_connections: vec![],
+			suspended_connections: vec![],
 		}
 	}
 
@@ -91,38 +116,306 @@ impl Module {
 		self.dependencies.get(&module_id.to_string())
 	}
 
+	// Exposing optional_dependencies
+	pub fn set_optional_dependencies(
+		&mut self,
+		optional_dependencies: HashMap<String, VersionReq>,
+	) {
+		self.optional_dependencies = optional_dependencies;
+	}
+	pub fn get_optional_dependencies(&self) -> &HashMap<String, VersionReq> {
+		&self.optional_dependencies
+	}
+	pub fn has_optional_dependency(&self, module_id: &str) -> bool {
+		self.optional_dependencies.contains_key(module_id)
+	}
+
 	// Exposing declared_functions
 	pub fn declare_function(&mut self, function_name: String) {
 		self.declared_functions.push(function_name);
 	}
+	pub fn undeclare_function(&mut self, function_name: &str) {
+		self.declared_functions
+			.retain(|declared_function| declared_function != function_name);
+		self.argument_schemas.remove(function_name);
+		self.response_schemas.remove(function_name);
+	}
 	pub fn is_function_declared(&self, function_name: &str) -> bool {
 		self.declared_functions.contains(&function_name.to_string())
 	}
+	pub fn set_function_schemas(
+		&mut self,
+		function_name: &str,
+		argument_schema: Option<Value>,
+		response_schema: Option<Value>,
+	) {
+		if let Some(argument_schema) = argument_schema {
+			self.argument_schemas
+				.insert(function_name.to_string(), argument_schema);
+		} else {
+			self.argument_schemas.remove(function_name);
+		}
+		if let Some(response_schema) = response_schema {
+			self.response_schemas
+				.insert(function_name.to_string(), response_schema);
+		} else {
+			self.response_schemas.remove(function_name);
+		}
+	}
+	pub fn get_argument_schema(&self, function_name: &str) -> Option<&Value> {
+		self.argument_schemas.get(function_name)
+	}
+	pub fn get_response_schema(&self, function_name: &str) -> Option<&Value> {
+		self.response_schemas.get(function_name)
+	}
+
+	// Forgets the functions, schemas and hooks of a previous version of the module
+	pub fn clear_declarations(&mut self) {
+		self.declared_functions.clear();
+		self.argument_schemas.clear();
+		self.response_schemas.clear();
+		self.registered_hooks.clear();
+	}
 
 	// Exposing registered_hooks
 	pub fn register_hook(&mut self, hook_name: String) {
 		self.registered_hooks.push(hook_name);
 	}
+	pub fn unregister_hook(&mut self, hook_name: &str) {
+		self.registered_hooks
+			.retain(|registered_hook| registered_hook != hook_name);
+	}
+	pub fn is_hook_pattern_registered(&self, hook_pattern: &str) -> bool {
+		self.registered_hooks.contains(&hook_pattern.to_string())
+	}
 	pub fn is_hook_registered(&self, hook_name: &str) -> bool {
-		self.registered_hooks.contains(&hook_name.to_string())
+		self.registered_hooks
+			.iter()
+			.any(|registered_hook| hook_pattern::matches(registered_hook, hook_name))
+	}
+
+	// Exposing replicable
+	pub fn is_replicable(&self) -> bool {
+		self.replicable
+	}
+	pub fn set_replicable(&mut self, replicable: bool) {
+		self.replicable = replicable;
 	}
 
-	pub async fn send(&self, data: String) {
-		let mut sender = &self.module_sender;
+	// Exposing backpressure
+	pub fn get_backpressure(&self) -> Backpressure {
+		self.backpressure
+	}
+	pub fn set_backpressure(&mut self, backpressure: Backpressure) {
+		self.backpressure = backpressure;
+		for (_, sender) in self.module_connections.iter() {
+			sender.set_backpressure(backpressure);
+		}
+	}
 
-		let result = sender.send(data).await;
-		if let Err(error) = result {
-			logger::error(&format!("Error queing data to module: {}", error));
+	// Exposing module_connections
+	pub fn add_connection(&mut self, module_uuid: u128, module_sender: OutboundSender) {
+		module_sender.set_backpressure(self.backpressure);
+		self.module_connections.push((module_uuid, module_sender));
+	}
+	pub fn remove_connection(&mut self, module_uuid: &u128) {
+		self.module_connections
+			.retain(|(connection_uuid, _)| connection_uuid != module_uuid);
+		self.draining_connections
+			.retain(|connection_uuid| connection_uuid != module_uuid);
+		self.suspended_connections
+			.retain(|connection_uuid| connection_uuid != module_uuid);
+		if &self.module_uuid == module_uuid {
+			if let Some(connection_uuid) = self
+				.get_active_connection_uuids()
+				.first()
+				.or_else(|| self.draining_connections.first())
+			{
+				self.module_uuid = *connection_uuid;
+			}
+		}
+	}
+	// Swaps out a connection of this module for another one, keeping its place
+	pub fn replace_connection(
+		&mut self,
+		module_uuid: &u128,
+		new_module_uuid: u128,
+		module_sender: OutboundSender,
+	) {
+		module_sender.set_backpressure(self.backpressure);
+		for connection in self.module_connections.iter_mut() {
+			if &connection.0 == module_uuid {
+				*connection = (new_module_uuid, module_sender);
+				break;
+			}
+		}
+		for connection_uuid in self.draining_connections.iter_mut() {
+			if connection_uuid == module_uuid {
+				*connection_uuid = new_module_uuid;
+			}
+		}
+		// The new connection is the one that resumed the suspended one
+		self.suspended_connections
+			.retain(|connection_uuid| connection_uuid != module_uuid);
+		if &self.module_uuid == module_uuid {
+			self.module_uuid = new_module_uuid;
+		}
+	}
+	pub fn has_connection(&self, module_uuid: &u128) -> bool {
+		self.module_connections
+			.iter()
+			.any(|(connection_uuid, _)| connection_uuid == module_uuid)
+	}
+	pub fn get_connection_uuids(&self) -> Vec<u128> {
+		self.module_connections
+			.iter()
+			.map(|(connection_uuid, _)| *connection_uuid)
+			.collect()
+	}
+	// How many messages are waiting to be written to each connection of this module
+	pub fn get_queue_depths(&self) -> Vec<(u128, usize)> {
+		self.module_connections
+			.iter()
+			.map(|(connection_uuid, sender)| (*connection_uuid, sender.get_depth()))
+			.collect()
+	}
+	// How many hook deliveries were dropped because a connection wasn't reading fast enough
+	pub fn get_dropped_message_count(&self) -> u64 {
+		self.module_connections
+			.iter()
+			.map(|(_, sender)| sender.get_dropped())
+			.sum()
+	}
+	pub fn get_active_connection_uuids(&self) -> Vec<u128> {
+		self.module_connections
+			.iter()
+			.map(|(connection_uuid, _)| *connection_uuid)
+			.filter(|connection_uuid| !self.is_connection_draining(connection_uuid))
+			.collect()
+	}
+
+	// Exposing draining_connections
+	pub fn drain_connection(&mut self, module_uuid: u128) {
+		if self.has_connection(&module_uuid) && !self.is_connection_draining(&module_uuid) {
+			self.draining_connections.push(module_uuid);
+		}
+	}
+	pub fn is_connection_draining(&self, module_uuid: &u128) -> bool {
+		self.draining_connections.contains(module_uuid)
+	}
+	pub fn get_draining_connection_uuids(&self) -> &Vec<u128> {
+		&self.draining_connections
+	}
+
+	// Exposing suspended_connections
+	pub fn suspend_connection(&mut self, module_uuid: &u128, module_sender: OutboundSender) {
+		self.replace_connection(module_uuid, *module_uuid, module_sender);
+		if self.has_connection(module_uuid) && !self.is_connection_suspended(module_uuid) {
+			self.suspended_connections.push(*module_uuid);
+		}
+	}
+	pub fn is_connection_suspended(&self, module_uuid: &u128) -> bool {
+		self.suspended_connections.contains(module_uuid)
+	}
+
+	// Sends the data to every connection of this module, except the draining ones.
+	// The data only counts as queued if it was queued for all of them
+	pub async fn send(&self, data: String) -> Delivery {
+		let mut delivery = Delivery::Queued;
+		for module_uuid in self.get_active_connection_uuids().iter() {
+			let connection_delivery = self.send_to(module_uuid, data.clone()).await;
+			if delivery == Delivery::Queued {
+				delivery = connection_delivery;
+			}
+		}
+		delivery
+	}
+
+	// Sends the data to a single connection of this module
+	pub async fn send_to(&self, module_uuid: &u128, data: String) -> Delivery {
+		self.queue_data(module_uuid, data, false).await
+	}
+
+	// Same as send, but never waits for room in the queues of the connections
+	pub fn force_send(&self, data: String) -> Delivery {
+		let mut delivery = Delivery::Queued;
+		for (module_uuid, sender) in self.module_connections.iter() {
+			if self.is_connection_draining(module_uuid) {
+				continue;
+			}
+			let result = sender.force_send(data.clone());
+			if let Err(error) = &result {
+				logger::error!("Error queing data to module: {}", error);
+			}
+			if delivery == Delivery::Queued {
+				delivery = Delivery::from(&result);
+			}
+		}
+		delivery
+	}
+
+	// Same as send, but the hook delivery can be dropped if the module isn't keeping up
+	pub async fn send_hook(&self, data: String) -> Delivery {
+		let mut delivery = Delivery::Queued;
+		for module_uuid in self.get_active_connection_uuids().iter() {
+			let connection_delivery = self.send_hook_to(module_uuid, data.clone()).await;
+			if delivery == Delivery::Queued {
+				delivery = connection_delivery;
+			}
+		}
+		delivery
+	}
+
+	// Same as send_to, but the hook delivery can be dropped if the module isn't keeping up
+	pub async fn send_hook_to(&self, module_uuid: &u128, data: String) -> Delivery {
+		self.queue_data(module_uuid, data, true).await
+	}
+
+	// Closes the sending queue of a single connection of this module
+	pub async fn close_connection(&self, module_uuid: &u128) {
+		let sender = self
+			.module_connections
+			.iter()
+			.find(|(connection_uuid, _)| connection_uuid == module_uuid);
+		if let Some((_, sender)) = sender {
+			sender.close();
 		}
 	}
 
 	pub async fn close_sender(&self) {
-		let mut sender = &self.module_sender;
+		for (_, sender) in self.module_connections.iter() {
+			sender.close();
+		}
+	}
+
+	async fn queue_data(&self, module_uuid: &u128, data: String, is_hook: bool) -> Delivery {
+		let sender = self
+			.module_connections
+			.iter()
+			.find(|(connection_uuid, _)| connection_uuid == module_uuid);
+		if sender.is_none() {
+			logger::error!(
+				"Connection {} does not belong to module '{}'",
+				module_uuid,
+				self.module_id
+			);
+			return Delivery::Closed;
+		}
+		let sender = &sender.unwrap().1;
 
-		let result = sender.close().await;
-		if let Err(error) = result {
-			logger::error(&format!("Error closing module's sending queue: {}", error));
-			return;
+		let result = if is_hook {
+			sender.send_hook(data).await
+		} else {
+			sender.send(data).await
+		};
+		match &result {
+			Err(SendError::Overflowed) => logger::warn!("Connection {} of module '{}' is not reading its data fast enough. Disconnected it, since its sending queue is full",
+				module_uuid, self.module_id),
+			// Dropped hook deliveries are counted, rather than logged one by one
+			Err(SendError::Dropped) => {}
+			Err(error) => logger::error!("Error queing data to module: {}", error),
+			Ok(_) => {}
 		}
+		Delivery::from(&result)
 	}
 }
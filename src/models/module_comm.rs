@@ -1,4 +1,8 @@
-use crate::{service::data_handler, utils::logger};
+use crate::{
+	models::{outbound_queue::SendError, Delivery, OutboundReceiver, OutboundSender},
+	service::data_handler,
+	utils::logger,
+};
 use juno::connection::Buffer;
 
 use futures::channel::mpsc::{UnboundedReceiver, UnboundedSender};
@@ -7,6 +11,7 @@ use futures_util::sink::SinkExt;
 #[cfg(target_family = "unix")]
 use async_std::os::unix::net::UnixStream;
 use async_std::{io::BufReader, net::TcpStream, prelude::*};
+use std::net::Shutdown;
 
 pub enum ModuleComm {
 	#[cfg(target_family = "unix")]
@@ -14,18 +19,18 @@ pub enum ModuleComm {
 		module_uuid: u128,
 
 		socket: UnixStream,
-		socket_sender: UnboundedSender<String>,
+		socket_sender: OutboundSender,
 	},
 	InetSocketComms {
 		module_uuid: u128,
 
 		socket: TcpStream,
-		socket_sender: UnboundedSender<String>,
+		socket_sender: OutboundSender,
 	},
 	InternalComms {
 		module_uuid: u128,
 		juno_sender: UnboundedSender<Buffer>,
-		socket_sender: UnboundedSender<String>,
+		socket_sender: OutboundSender,
 	},
 }
 
@@ -34,7 +39,7 @@ impl ModuleComm {
 	pub fn new_unix_comm(
 		module_uuid: u128,
 		socket: UnixStream,
-		socket_sender: UnboundedSender<String>,
+		socket_sender: OutboundSender,
 	) -> Self {
 		ModuleComm::UnixSocketComms {
 			module_uuid,
@@ -45,7 +50,7 @@ impl ModuleComm {
 	pub fn new_inet_comm(
 		module_uuid: u128,
 		socket: TcpStream,
-		socket_sender: UnboundedSender<String>,
+		socket_sender: OutboundSender,
 	) -> Self {
 		ModuleComm::InetSocketComms {
 			module_uuid,
@@ -56,7 +61,7 @@ impl ModuleComm {
 	pub fn new_internal_comm(
 		module_uuid: u128,
 		juno_sender: UnboundedSender<Buffer>,
-		socket_sender: UnboundedSender<String>,
+		socket_sender: OutboundSender,
 	) -> Self {
 		ModuleComm::InternalComms {
 			module_uuid,
@@ -74,7 +79,12 @@ impl ModuleComm {
 		}
 	}
 
-	pub fn clone_sender(&self) -> UnboundedSender<String> {
+	// Internal connections are made by juno itself
+	pub fn is_internal(&self) -> bool {
+		matches!(self, ModuleComm::InternalComms { .. })
+	}
+
+	pub fn clone_sender(&self) -> OutboundSender {
 		match self {
 			#[cfg(target_family = "unix")]
 			ModuleComm::UnixSocketComms { socket_sender, .. } => socket_sender.clone(),
@@ -83,31 +93,37 @@ impl ModuleComm {
 		}
 	}
 
-	pub async fn send(&self, data: String) {
-		let mut sender = match self {
+	pub fn get_sender(&self) -> &OutboundSender {
+		match self {
 			#[cfg(target_family = "unix")]
 			ModuleComm::UnixSocketComms { socket_sender, .. } => socket_sender,
 			ModuleComm::InetSocketComms { socket_sender, .. } => socket_sender,
 			ModuleComm::InternalComms { socket_sender, .. } => socket_sender,
-		};
-		let result = sender.send(data).await;
-		if let Err(error) = result {
-			logger::error(&format!("Error queing data to module: {}", error));
 		}
 	}
 
-	pub async fn close_sender(&self) {
-		let mut sender = match self {
-			#[cfg(target_family = "unix")]
-			ModuleComm::UnixSocketComms { socket_sender, .. } => socket_sender,
-			ModuleComm::InetSocketComms { socket_sender, .. } => socket_sender,
-			ModuleComm::InternalComms { socket_sender, .. } => socket_sender,
-		};
-		let result = sender.close().await;
-		if let Err(error) = result {
-			logger::error(&format!("Error closing module's sending queue: {}", error));
-			return;
+	pub async fn send(&self, data: String) -> Delivery {
+		let result = self.get_sender().send(data).await;
+		match &result {
+			Err(SendError::Overflowed) => logger::warn!("Connection {} is not reading its data fast enough. Disconnected it, since its sending queue is full",
+				self.get_uuid()),
+			Err(error) => logger::error!("Error queing data to module: {}", error),
+			Ok(_) => {}
 		}
+		Delivery::from(&result)
+	}
+
+	// Same as send, but never waits for room in the queue
+	pub fn force_send(&self, data: String) -> Delivery {
+		let result = self.get_sender().force_send(data);
+		if let Err(error) = &result {
+			logger::error!("Error queing data to module: {}", error);
+		}
+		Delivery::from(&result)
+	}
+
+	pub async fn close_sender(&self) {
+		self.get_sender().close();
 	}
 
 	pub async fn read_data_loop(&self) {
@@ -156,7 +172,7 @@ impl ModuleComm {
 		}
 	}
 
-	pub async fn write_data_loop(&self, receiver: &mut UnboundedReceiver<String>) {
+	pub async fn write_data_loop(&self, receiver: &mut OutboundReceiver) {
 		match self {
 			#[cfg(target_family = "unix")]
 			ModuleComm::UnixSocketComms { socket, .. } => {
@@ -164,25 +180,33 @@ impl ModuleComm {
 
 				while let Some(data) = receiver.next().await {
 					if let Err(err) = socket.write_all(data.as_bytes()).await {
-						logger::error(&format!("Error while writing to socket: {}", err));
+						logger::error!("Error while writing to socket: {}", err);
 					}
 				}
+				// The sending queue was closed. Close the socket too, in case the module is still connected
+				if let Err(err) = socket.shutdown(Shutdown::Both) {
+					logger::verbose!("Socket is already closed: {}", err);
+				}
 			}
 			ModuleComm::InetSocketComms { socket, .. } => {
 				let mut socket = socket;
 
 				while let Some(data) = receiver.next().await {
 					if let Err(err) = socket.write_all(data.as_bytes()).await {
-						logger::error(&format!("Error while writing to socket: {}", err));
+						logger::error!("Error while writing to socket: {}", err);
 					}
 				}
+				// The sending queue was closed. Close the socket too, in case the module is still connected
+				if let Err(err) = socket.shutdown(Shutdown::Both) {
+					logger::verbose!("Socket is already closed: {}", err);
+				}
 			}
 			ModuleComm::InternalComms { juno_sender, .. } => {
 				let mut sender = juno_sender;
 
 				while let Some(data) = receiver.next().await {
 					if let Err(err) = sender.send(data.as_bytes().to_vec()).await {
-						logger::error(&format!("Error while writing to socket: {}", err));
+						logger::error!("Error while writing to socket: {}", err);
 					}
 				}
 			}
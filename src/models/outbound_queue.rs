@@ -0,0 +1,426 @@
+use crate::utils::constants::backpressure;
+
+use std::{
+	collections::VecDeque,
+	fmt,
+	pin::Pin,
+	sync::{Arc, Mutex},
+	task::{Context, Poll, Waker},
+};
+
+use futures::{future::poll_fn, stream::Stream};
+
+// What happens when something is sent to a connection whose queue is full
+#[derive(Clone, Copy, PartialEq)]
+pub enum Backpressure {
+	// The sender waits until the connection has read enough for the data to fit
+	Block,
+	// The oldest hook deliveries are dropped to make room. If there are none, the sender waits
+	DropOldest,
+	// The connection is closed, along with everything that was queued for it
+	Disconnect,
+}
+
+impl Backpressure {
+	pub fn parse(name: &str) -> Option<Self> {
+		match name {
+			backpressure::BLOCK => Some(Backpressure::Block),
+			backpressure::DROP_OLDEST => Some(Backpressure::DropOldest),
+			backpressure::DISCONNECT => Some(Backpressure::Disconnect),
+			_ => None,
+		}
+	}
+
+	pub fn get_name(&self) -> &'static str {
+		match self {
+			Backpressure::Block => backpressure::BLOCK,
+			Backpressure::DropOldest => backpressure::DROP_OLDEST,
+			Backpressure::Disconnect => backpressure::DISCONNECT,
+		}
+	}
+}
+
+// What became of data that was sent to a module
+#[derive(Clone, Copy, PartialEq)]
+pub enum Delivery {
+	// It's waiting to be written to the connection
+	Queued,
+	// The connection's queue was full, so it was dropped
+	Dropped,
+	// The connection is closed, or was closed because its queue was full
+	Closed,
+}
+
+impl Delivery {
+	pub fn get_name(&self) -> &'static str {
+		match self {
+			Delivery::Queued => "queued",
+			Delivery::Dropped => "dropped",
+			Delivery::Closed => "closed",
+		}
+	}
+}
+
+impl From<&Result<(), SendError>> for Delivery {
+	fn from(result: &Result<(), SendError>) -> Self {
+		match result {
+			Ok(_) => Delivery::Queued,
+			Err(SendError::Full) | Err(SendError::Dropped) => Delivery::Dropped,
+			Err(SendError::Closed) | Err(SendError::Overflowed) => Delivery::Closed,
+		}
+	}
+}
+
+pub enum SendError {
+	// The connection's queue was already closed
+	Closed,
+	// The queue was full, and the connection was closed because of it
+	Overflowed,
+	// The queue was full, and the data wasn't queued
+	Full,
+	// The queue was full of anything but hook deliveries, so the hook delivery itself was dropped
+	Dropped,
+}
+
+impl fmt::Display for SendError {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			SendError::Closed => write!(f, "the sending queue is closed"),
+			SendError::Overflowed => write!(f, "the sending queue is full"),
+			SendError::Full => write!(f, "the sending queue has no room"),
+			SendError::Dropped => write!(f, "the sending queue is full of newer data"),
+		}
+	}
+}
+
+struct Queue {
+	// Every message, along with whether it is a hook delivery that can be dropped
+	messages: VecDeque<(String, bool)>,
+	// 0 means the queue can grow without limit
+	limit: usize,
+	backpressure: Backpressure,
+	closed: bool,
+	senders: usize,
+	dropped: u64,
+	receiver_waker: Option<Waker>,
+	sender_wakers: Vec<Waker>,
+}
+
+impl Queue {
+	fn push(&mut self, data: String, droppable: bool) {
+		self.messages.push_back((data, droppable));
+		if let Some(waker) = self.receiver_waker.take() {
+			waker.wake();
+		}
+	}
+
+	fn close(&mut self) {
+		self.closed = true;
+		if let Some(waker) = self.receiver_waker.take() {
+			waker.wake();
+		}
+		self.wake_senders();
+	}
+
+	fn wake_senders(&mut self) {
+		for waker in self.sender_wakers.drain(..) {
+			waker.wake();
+		}
+	}
+}
+
+// Creates the queue of data waiting to be written to a connection
+pub fn channel(limit: usize, backpressure: Backpressure) -> (OutboundSender, OutboundReceiver) {
+	let queue = Arc::new(Mutex::new(Queue {
+		messages: VecDeque::new(),
+		limit,
+		backpressure,
+		closed: false,
+		senders: 1,
+		dropped: 0,
+		receiver_waker: None,
+		sender_wakers: vec![],
+	}));
+	(
+		OutboundSender {
+			queue: queue.clone(),
+		},
+		OutboundReceiver { queue },
+	)
+}
+
+pub struct OutboundSender {
+	queue: Arc<Mutex<Queue>>,
+}
+
+#[allow(dead_code)]
+impl OutboundSender {
+	pub async fn send(&self, data: String) -> Result<(), SendError> {
+		self.queue_data(data, false).await
+	}
+
+	// Hook deliveries are the only thing that can be dropped when the queue is full
+	pub async fn send_hook(&self, data: String) -> Result<(), SendError> {
+		self.queue_data(data, true).await
+	}
+
+	// Queues the data only if there is room for it right away, regardless of the backpressure
+	pub fn try_send(&self, data: String) -> Result<(), SendError> {
+		let mut queue = self.queue.lock().unwrap();
+		if queue.closed {
+			return Err(SendError::Closed);
+		}
+		if queue.limit != 0 && queue.messages.len() >= queue.limit {
+			return Err(SendError::Full);
+		}
+		queue.push(data, false);
+		Ok(())
+	}
+
+	// Queues the data without waiting, even if the queue is full. Only meant for what juno itself
+	// has to tell a module while holding on to the other modules, which nobody should wait on
+	pub fn force_send(&self, data: String) -> Result<(), SendError> {
+		let mut queue = self.queue.lock().unwrap();
+		if queue.closed {
+			return Err(SendError::Closed);
+		}
+		queue.push(data, false);
+		Ok(())
+	}
+
+	// Stops accepting data. Whatever is already queued is still written
+	pub fn close(&self) {
+		self.queue.lock().unwrap().close();
+	}
+
+	pub fn get_depth(&self) -> usize {
+		self.queue.lock().unwrap().messages.len()
+	}
+
+	pub fn get_limit(&self) -> usize {
+		self.queue.lock().unwrap().limit
+	}
+
+	pub fn get_dropped(&self) -> u64 {
+		self.queue.lock().unwrap().dropped
+	}
+
+	pub fn get_backpressure(&self) -> Backpressure {
+		self.queue.lock().unwrap().backpressure
+	}
+	pub fn set_backpressure(&self, backpressure: Backpressure) {
+		let mut queue = self.queue.lock().unwrap();
+		queue.backpressure = backpressure;
+		// Blocked senders might not have to wait anymore
+		queue.wake_senders();
+	}
+
+	async fn queue_data(&self, data: String, droppable: bool) -> Result<(), SendError> {
+		let mut data = Some(data);
+		poll_fn(|context| {
+			let mut queue = self.queue.lock().unwrap();
+			if queue.closed {
+				return Poll::Ready(Err(SendError::Closed));
+			}
+			if queue.limit == 0 || queue.messages.len() < queue.limit {
+				queue.push(data.take().unwrap(), droppable);
+				return Poll::Ready(Ok(()));
+			}
+
+			match queue.backpressure {
+				Backpressure::Block => {}
+				Backpressure::DropOldest => {
+					let oldest_hook = queue.messages.iter().position(|(_, droppable)| *droppable);
+					if let Some(position) = oldest_hook {
+						queue.messages.remove(position);
+						queue.dropped += 1;
+						queue.push(data.take().unwrap(), droppable);
+						return Poll::Ready(Ok(()));
+					} else if droppable {
+						// There is no older hook delivery to drop than this one
+						queue.dropped += 1;
+						return Poll::Ready(Err(SendError::Dropped));
+					}
+				}
+				Backpressure::Disconnect => {
+					queue.dropped += queue.messages.len() as u64 + 1;
+					queue.messages.clear();
+					queue.close();
+					return Poll::Ready(Err(SendError::Overflowed));
+				}
+			}
+
+			queue.sender_wakers.push(context.waker().clone());
+			Poll::Pending
+		})
+		.await
+	}
+}
+
+impl Clone for OutboundSender {
+	fn clone(&self) -> Self {
+		self.queue.lock().unwrap().senders += 1;
+		OutboundSender {
+			queue: self.queue.clone(),
+		}
+	}
+}
+
+impl Drop for OutboundSender {
+	fn drop(&mut self) {
+		let mut queue = self.queue.lock().unwrap();
+		queue.senders -= 1;
+		if queue.senders == 0 {
+			if let Some(waker) = queue.receiver_waker.take() {
+				waker.wake();
+			}
+		}
+	}
+}
+
+pub struct OutboundReceiver {
+	queue: Arc<Mutex<Queue>>,
+}
+
+impl OutboundReceiver {
+	// Takes the next message, without waiting for one if the queue is empty
+	pub fn try_next(&mut self) -> Option<String> {
+		let mut queue = self.queue.lock().unwrap();
+		let message = queue.messages.pop_front();
+		if message.is_some() {
+			queue.wake_senders();
+		}
+		message.map(|(data, _)| data)
+	}
+}
+
+impl Stream for OutboundReceiver {
+	type Item = String;
+
+	fn poll_next(self: Pin<&mut Self>, context: &mut Context<'_>) -> Poll<Option<String>> {
+		let mut queue = self.queue.lock().unwrap();
+		if let Some((data, _)) = queue.messages.pop_front() {
+			queue.wake_senders();
+			return Poll::Ready(Some(data));
+		}
+		if queue.closed || queue.senders == 0 {
+			return Poll::Ready(None);
+		}
+		queue.receiver_waker = Some(context.waker().clone());
+		Poll::Pending
+	}
+}
+
+impl Drop for OutboundReceiver {
+	fn drop(&mut self) {
+		// Nothing is going to be read anymore, so nobody should wait for room
+		self.queue.lock().unwrap().close();
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use futures::{FutureExt, StreamExt};
+
+	fn send(sender: &OutboundSender, data: &str) -> Option<Result<(), SendError>> {
+		sender.send(String::from(data)).now_or_never()
+	}
+
+	fn send_hook(sender: &OutboundSender, data: &str) -> Option<Result<(), SendError>> {
+		sender.send_hook(String::from(data)).now_or_never()
+	}
+
+	fn drain(receiver: &mut OutboundReceiver) -> Vec<String> {
+		std::iter::from_fn(|| receiver.try_next()).collect()
+	}
+
+	#[test]
+	fn block_waits_for_room() {
+		let (sender, mut receiver) = channel(1, Backpressure::Block);
+		assert!(matches!(send(&sender, "a"), Some(Ok(()))));
+		assert!(send(&sender, "b").is_none());
+		assert!(send_hook(&sender, "c").is_none());
+
+		assert_eq!(receiver.try_next(), Some(String::from("a")));
+		assert!(matches!(send(&sender, "b"), Some(Ok(()))));
+		assert_eq!(drain(&mut receiver), vec!["b"]);
+		assert_eq!(sender.get_dropped(), 0);
+	}
+
+	#[test]
+	fn drop_oldest_drops_only_hooks() {
+		let (sender, mut receiver) = channel(2, Backpressure::DropOldest);
+		assert!(matches!(send_hook(&sender, "hook 1"), Some(Ok(()))));
+		assert!(matches!(send(&sender, "call 1"), Some(Ok(()))));
+		assert!(matches!(send_hook(&sender, "hook 2"), Some(Ok(()))));
+		assert!(matches!(send(&sender, "call 2"), Some(Ok(()))));
+		assert_eq!(sender.get_dropped(), 2);
+
+		// With no older hook to drop, a hook is dropped, and anything else waits
+		assert!(matches!(
+			send_hook(&sender, "hook 3"),
+			Some(Err(SendError::Dropped))
+		));
+		assert_eq!(sender.get_dropped(), 3);
+		assert!(send(&sender, "call 3").is_none());
+		assert_eq!(drain(&mut receiver), vec!["call 1", "call 2"]);
+	}
+
+	#[test]
+	fn disconnect_closes_the_queue() {
+		let (sender, mut receiver) = channel(1, Backpressure::Disconnect);
+		assert!(matches!(send(&sender, "a"), Some(Ok(()))));
+		assert!(matches!(
+			send(&sender, "b"),
+			Some(Err(SendError::Overflowed))
+		));
+		assert!(matches!(send(&sender, "c"), Some(Err(SendError::Closed))));
+		assert_eq!(sender.get_dropped(), 2);
+		assert_eq!(receiver.next().now_or_never(), Some(None));
+	}
+
+	#[test]
+	fn force_send_never_waits() {
+		let (sender, mut receiver) = channel(1, Backpressure::Block);
+		assert!(matches!(send(&sender, "a"), Some(Ok(()))));
+		assert!(sender.force_send(String::from("b")).is_ok());
+		assert_eq!(drain(&mut receiver), vec!["a", "b"]);
+
+		sender.close();
+		assert!(matches!(
+			sender.force_send(String::from("c")),
+			Err(SendError::Closed)
+		));
+	}
+
+	#[test]
+	fn try_send_needs_room() {
+		let (sender, mut receiver) = channel(1, Backpressure::Disconnect);
+		assert!(sender.try_send(String::from("a")).is_ok());
+		assert!(matches!(
+			sender.try_send(String::from("b")),
+			Err(SendError::Full)
+		));
+		assert_eq!(drain(&mut receiver), vec!["a"]);
+		assert!(sender.try_send(String::from("c")).is_ok());
+	}
+
+	#[test]
+	fn unlimited_queues_never_wait() {
+		let (sender, mut receiver) = channel(0, Backpressure::Block);
+		for _ in 0..100 {
+			assert!(matches!(send(&sender, "a"), Some(Ok(()))));
+		}
+		assert_eq!(sender.get_depth(), 100);
+		assert_eq!(drain(&mut receiver).len(), 100);
+	}
+
+	#[test]
+	fn dropping_the_receiver_closes_the_queue() {
+		let (sender, receiver) = channel(1, Backpressure::Block);
+		assert!(matches!(send(&sender, "a"), Some(Ok(()))));
+		drop(receiver);
+		assert!(matches!(send(&sender, "b"), Some(Err(SendError::Closed))));
+	}
+}
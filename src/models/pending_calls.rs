@@ -0,0 +1,160 @@
+use crate::models::RequestOrigin;
+
+use std::collections::HashMap;
+
+// The function calls waiting for a response, by requestId. Also counts how many of them every
+// connection has yet to respond to, so that picking the least busy replica doesn't have to go
+// through every call
+#[derive(Default)]
+pub struct PendingCalls {
+	origins: HashMap<String, RequestOrigin>,
+	outstanding_calls: HashMap<u128, usize>,
+}
+
+impl PendingCalls {
+	pub fn get(&self, request_id: &str) -> Option<&RequestOrigin> {
+		self.origins.get(request_id)
+	}
+
+	pub fn contains_key(&self, request_id: &str) -> bool {
+		self.origins.contains_key(request_id)
+	}
+
+	pub fn iter(&self) -> impl Iterator<Item = (&String, &RequestOrigin)> {
+		self.origins.iter()
+	}
+
+	// Returns the call that had the same requestId, if any
+	pub fn insert(&mut self, request_id: String, origin: RequestOrigin) -> Option<RequestOrigin> {
+		*self
+			.outstanding_calls
+			.entry(origin.target_module_uuid)
+			.or_insert(0) += 1;
+		let previous_origin = self.origins.insert(request_id, origin);
+		if let Some(previous_origin) = &previous_origin {
+			forget_call(
+				&mut self.outstanding_calls,
+				previous_origin.target_module_uuid,
+			);
+		}
+		previous_origin
+	}
+
+	pub fn remove(&mut self, request_id: &str) -> Option<RequestOrigin> {
+		let origin = self.origins.remove(request_id)?;
+		forget_call(&mut self.outstanding_calls, origin.target_module_uuid);
+		Some(origin)
+	}
+
+	// Removes every call that the given function returns false for
+	pub fn retain(&mut self, mut keep: impl FnMut(&RequestOrigin) -> bool) {
+		let outstanding_calls = &mut self.outstanding_calls;
+		self.origins.retain(|_, origin| {
+			if keep(origin) {
+				return true;
+			}
+			forget_call(outstanding_calls, origin.target_module_uuid);
+			false
+		});
+	}
+
+	// The calls made by, and made to, a connection now belong to another one
+	pub fn move_connection(&mut self, from_uuid: &u128, to_uuid: u128) {
+		for origin in self.origins.values_mut() {
+			if origin.module_uuid == *from_uuid {
+				origin.set_module_uuid(to_uuid);
+			}
+			if origin.target_module_uuid == *from_uuid {
+				origin.set_target_module_uuid(to_uuid);
+			}
+		}
+		if let Some(count) = self.outstanding_calls.remove(from_uuid) {
+			*self.outstanding_calls.entry(to_uuid).or_insert(0) += count;
+		}
+	}
+
+	// How many calls a connection has yet to respond to
+	pub fn get_outstanding_calls(&self, module_uuid: &u128) -> usize {
+		self.outstanding_calls
+			.get(module_uuid)
+			.copied()
+			.unwrap_or(0)
+	}
+}
+
+fn forget_call(outstanding_calls: &mut HashMap<u128, usize>, target_module_uuid: u128) {
+	if let Some(count) = outstanding_calls.get_mut(&target_module_uuid) {
+		*count -= 1;
+		if *count == 0 {
+			outstanding_calls.remove(&target_module_uuid);
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::utils::trace::{Span, TraceContext};
+
+	fn call_to(target_module_uuid: u128) -> RequestOrigin {
+		RequestOrigin::new(
+			String::from("caller"),
+			1,
+			target_module_uuid,
+			String::from("callee.function"),
+			0,
+			Span::start(&TraceContext::new_root(), String::from("test")),
+		)
+	}
+
+	#[test]
+	fn counts_calls_per_connection() {
+		let mut calls = PendingCalls::default();
+		calls.insert(String::from("a"), call_to(2));
+		calls.insert(String::from("b"), call_to(2));
+		calls.insert(String::from("c"), call_to(3));
+		assert_eq!(calls.get_outstanding_calls(&2), 2);
+		assert_eq!(calls.get_outstanding_calls(&3), 1);
+		assert_eq!(calls.get_outstanding_calls(&4), 0);
+
+		calls.remove("a");
+		assert_eq!(calls.get_outstanding_calls(&2), 1);
+		assert!(calls.remove("a").is_none());
+		assert_eq!(calls.get_outstanding_calls(&2), 1);
+	}
+
+	#[test]
+	fn replacing_a_call_moves_its_count() {
+		let mut calls = PendingCalls::default();
+		calls.insert(String::from("a"), call_to(2));
+		assert!(calls.insert(String::from("a"), call_to(3)).is_some());
+		assert_eq!(calls.get_outstanding_calls(&2), 0);
+		assert_eq!(calls.get_outstanding_calls(&3), 1);
+	}
+
+	#[test]
+	fn retain_forgets_removed_calls() {
+		let mut calls = PendingCalls::default();
+		calls.insert(String::from("a"), call_to(2));
+		calls.insert(String::from("b"), call_to(3));
+		calls.retain(|origin| origin.target_module_uuid != 2);
+		assert!(!calls.contains_key("a"));
+		assert!(calls.contains_key("b"));
+		assert_eq!(calls.get_outstanding_calls(&2), 0);
+		assert_eq!(calls.get_outstanding_calls(&3), 1);
+	}
+
+	#[test]
+	fn moves_calls_to_a_new_connection() {
+		let mut calls = PendingCalls::default();
+		calls.insert(String::from("a"), call_to(2));
+		calls.insert(String::from("b"), call_to(5));
+		calls.move_connection(&2, 5);
+		assert_eq!(calls.get_outstanding_calls(&2), 0);
+		assert_eq!(calls.get_outstanding_calls(&5), 2);
+		assert_eq!(calls.get("a").unwrap().target_module_uuid, 5);
+
+		calls.move_connection(&1, 7);
+		assert_eq!(calls.get("b").unwrap().module_uuid, 7);
+	}
+}
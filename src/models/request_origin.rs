@@ -0,0 +1,73 @@
+use crate::utils::trace::Span;
+
+#[derive(Clone)]
+// Keeps track of who made a function call, so that the response can be routed back
+pub struct RequestOrigin {
+	pub(crate) module_id: String,
+	// The connection of the module that made the call. Modules with replicas have more than one
+	pub(crate) module_uuid: u128,
+	// The connection that the function call was sent to, and which is expected to respond
+	pub(crate) target_module_uuid: u128,
+	// The function that was called, as 'module.function'
+	pub(crate) function: String,
+	// Unique for every function call. Used to make sure a timeout only expires the call it was started for
+	pub(crate) call_id: u64,
+	// The span of the call, from when it was sent to the target module until it is done with
+	pub(crate) span: Span,
+}
+
+impl RequestOrigin {
+	pub fn new(
+		module_id: String,
+		module_uuid: u128,
+		target_module_uuid: u128,
+		function: String,
+		call_id: u64,
+		span: Span,
+	) -> Self {
+		RequestOrigin {
+			module_id,
+			module_uuid,
+			target_module_uuid,
+			function,
+			call_id,
+			span,
+		}
+	}
+
+	// Exposing module_id
+	pub fn get_module_id(&self) -> &String {
+		&self.module_id
+	}
+
+	// Exposing module_uuid
+	pub fn get_module_uuid(&self) -> &u128 {
+		&self.module_uuid
+	}
+	pub fn set_module_uuid(&mut self, module_uuid: u128) {
+		self.module_uuid = module_uuid;
+	}
+
+	// Exposing target_module_uuid
+	pub fn get_target_module_uuid(&self) -> &u128 {
+		&self.target_module_uuid
+	}
+	pub fn set_target_module_uuid(&mut self, target_module_uuid: u128) {
+		self.target_module_uuid = target_module_uuid;
+	}
+
+	// Exposing function
+	pub fn get_function(&self) -> &String {
+		&self.function
+	}
+
+	// Exposing call_id
+	pub fn get_call_id(&self) -> &u64 {
+		&self.call_id
+	}
+
+	// Exposing span
+	pub fn get_span(&self) -> &Span {
+		&self.span
+	}
+}
@@ -0,0 +1,29 @@
+use crate::models::OutboundReceiver;
+
+// A connection of a module that disconnected, but can still be resumed with its resumption token.
+// Everything sent to the connection in the meantime is queued, and delivered once it is resumed
+pub struct SuspendedConnection {
+	pub(crate) module_id: String,
+	pub(crate) module_uuid: u128,
+	pub(crate) queued_messages: OutboundReceiver,
+}
+
+impl SuspendedConnection {
+	pub fn new(module_id: String, module_uuid: u128, queued_messages: OutboundReceiver) -> Self {
+		SuspendedConnection {
+			module_id,
+			module_uuid,
+			queued_messages,
+		}
+	}
+
+	// Exposing module_id
+	pub fn get_module_id(&self) -> &String {
+		&self.module_id
+	}
+
+	// Exposing module_uuid
+	pub fn get_module_uuid(&self) -> &u128 {
+		&self.module_uuid
+	}
+}
@@ -0,0 +1,115 @@
+use std::time::{Duration, Instant};
+
+// Limits how much of something can be used per second, while still allowing short bursts.
+// The bucket holds at most a second's worth of tokens, and is refilled at the rate it's used with.
+// The rate is passed in every time, so that it can be changed without losing track of the usage
+pub struct TokenBucket {
+	tokens: f64,
+	last_refill: Instant,
+	rejected: u64,
+}
+
+impl TokenBucket {
+	pub fn new(rate: u64) -> Self {
+		TokenBucket {
+			tokens: rate as f64,
+			last_refill: Instant::now(),
+			rejected: 0,
+		}
+	}
+
+	// Checks if the tokens are available, without taking them. Otherwise, returns how long to wait
+	// for them. A rate of 0 means there is no limit. Anything bigger than the whole bucket is let
+	// through once the bucket is full
+	pub fn check(&mut self, amount: u64, rate: u64) -> Result<(), Duration> {
+		if rate == 0 {
+			return Ok(());
+		}
+		self.refill(rate);
+
+		let required = amount.min(rate) as f64;
+		if self.tokens >= required {
+			Ok(())
+		} else {
+			self.rejected += 1;
+			Err(Duration::from_secs_f64(
+				(required - self.tokens) / rate as f64,
+			))
+		}
+	}
+
+	// Takes the tokens, once they've been checked. Taking more than the whole bucket leaves it in debt
+	pub fn take(&mut self, amount: u64, rate: u64) {
+		if rate == 0 {
+			return;
+		}
+		self.refill(rate);
+		self.tokens -= amount as f64;
+	}
+
+	// Exposing tokens
+	pub fn get_remaining(&mut self, rate: u64) -> u64 {
+		self.refill(rate);
+		self.tokens.max(0.0) as u64
+	}
+
+	// Exposing rejected
+	pub fn get_rejected(&self) -> u64 {
+		self.rejected
+	}
+
+	fn refill(&mut self, rate: u64) {
+		let now = Instant::now();
+		let elapsed = now.duration_since(self.last_refill).as_secs_f64();
+		self.tokens = (self.tokens + elapsed * rate as f64).min(rate as f64);
+		self.last_refill = now;
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn allows_a_second_worth_of_tokens() {
+		let mut bucket = TokenBucket::new(10);
+		assert!(bucket.check(10, 10).is_ok());
+		bucket.take(4, 10);
+		assert!(bucket.check(6, 10).is_ok());
+		bucket.take(6, 10);
+
+		let retry_after = bucket.check(1, 10).unwrap_err();
+		assert!(retry_after > Duration::from_millis(0));
+		assert!(retry_after <= Duration::from_millis(100));
+		assert_eq!(bucket.get_rejected(), 1);
+	}
+
+	#[test]
+	fn checking_takes_nothing() {
+		let mut bucket = TokenBucket::new(10);
+		for _ in 0..100 {
+			assert!(bucket.check(10, 10).is_ok());
+		}
+		assert_eq!(bucket.get_remaining(10), 10);
+		assert_eq!(bucket.get_rejected(), 0);
+	}
+
+	#[test]
+	fn large_amounts_leave_the_bucket_in_debt() {
+		let mut bucket = TokenBucket::new(10);
+		assert!(bucket.check(25, 10).is_ok());
+		bucket.take(25, 10);
+		assert_eq!(bucket.get_remaining(10), 0);
+
+		let retry_after = bucket.check(1, 10).unwrap_err();
+		assert!(retry_after > Duration::from_secs(1));
+	}
+
+	#[test]
+	fn a_rate_of_zero_is_unlimited() {
+		let mut bucket = TokenBucket::new(0);
+		bucket.take(1000, 0);
+		assert!(bucket.check(1000, 0).is_ok());
+		assert_eq!(bucket.get_rejected(), 0);
+	}
+}
@@ -0,0 +1,95 @@
+use crate::utils::constants::{dependency_reasons, request_keys};
+
+use semver::{Version, VersionReq};
+use serde_json::{json, Value};
+
+#[derive(Clone)]
+// The reason a single dependency of a module is not satisfied
+pub enum UnmetDependency {
+	// The dependency isn't connected to juno
+	Missing {
+		module_id: String,
+	},
+	// The dependency is connected, but its version doesn't match the requirement
+	VersionMismatch {
+		module_id: String,
+		required: VersionReq,
+		present: Version,
+	},
+	// The dependency depends (directly or indirectly) on the module itself.
+	// The path starts and ends with the module
+	Cycle {
+		module_id: String,
+		path: Vec<String>,
+	},
+	// The dependency is connected, but its own dependencies are not satisfied
+	DependencyUnmet {
+		module_id: String,
+	},
+}
+
+impl UnmetDependency {
+	pub fn get_module_id(&self) -> &String {
+		match self {
+			UnmetDependency::Missing { module_id } => module_id,
+			UnmetDependency::VersionMismatch { module_id, .. } => module_id,
+			UnmetDependency::Cycle { module_id, .. } => module_id,
+			UnmetDependency::DependencyUnmet { module_id } => module_id,
+		}
+	}
+
+	pub fn get_reason(&self) -> &str {
+		match self {
+			UnmetDependency::Missing { .. } => dependency_reasons::MISSING,
+			UnmetDependency::VersionMismatch { .. } => dependency_reasons::VERSION_MISMATCH,
+			UnmetDependency::Cycle { .. } => dependency_reasons::CYCLE,
+			UnmetDependency::DependencyUnmet { .. } => dependency_reasons::DEPENDENCY_UNMET,
+		}
+	}
+
+	pub fn get_description(&self) -> String {
+		match self {
+			UnmetDependency::Missing { module_id } => {
+				format!("Dependency '{}' is not present", module_id)
+			}
+			UnmetDependency::VersionMismatch {
+				module_id,
+				required,
+				present,
+			} => format!(
+				"Dependency '{}' is incompatible. Required '{}', present '{}'",
+				module_id, required, present
+			),
+			UnmetDependency::Cycle { module_id, path } => format!(
+				"Dependency '{}' is part of a dependency cycle: {}",
+				module_id,
+				path.join(" -> ")
+			),
+			UnmetDependency::DependencyUnmet { module_id } => format!(
+				"Dependency '{}' is present, but its own dependencies are not satisfied",
+				module_id
+			),
+		}
+	}
+
+	pub fn to_json(&self) -> Value {
+		let mut value = json!({
+			request_keys::MODULE_ID: self.get_module_id(),
+			request_keys::REASON: self.get_reason(),
+			request_keys::MESSAGE: self.get_description()
+		});
+		match self {
+			UnmetDependency::VersionMismatch {
+				required, present, ..
+			} => {
+				value[request_keys::REQUIRED] = Value::String(required.to_string());
+				value[request_keys::PRESENT] = Value::String(present.to_string());
+			}
+			UnmetDependency::Cycle { path, .. } => {
+				value[request_keys::CYCLE] = json!(path);
+			}
+			_ => {}
+		}
+		value
+	}
+}
@@ -1,34 +1,102 @@
 use crate::{
-	models::{Module, ModuleComm},
+	models::{
+		outbound_queue, Backpressure, Delivery, Module, ModuleComm, OutboundReceiver,
+		OutboundSender, PendingCalls, RequestOrigin, SuspendedConnection, TokenBucket,
+		UnmetDependency,
+	},
+	service::{dependency_graph, metrics},
 	utils::{
-		constants::{self, errors, juno_hooks, request_keys, request_types},
-		logger,
+		constants::{self, errors, juno_hooks, rate_limits, request_keys, request_types},
+		credentials::{self, Credential},
+		hook_pattern,
+		json_schema::{self, ValidationError},
+		logger::{self, Fields},
+		policy::{Policy, RateLimits},
+		trace::{Span, TraceContext},
 	},
 };
 
-use async_std::sync::RwLock;
+use async_std::{
+	sync::{RwLock, RwLockWriteGuard},
+	task,
+};
 use std::{
 	collections::HashMap,
-	time::{SystemTime, UNIX_EPOCH},
+	sync::atomic::{AtomicU64, AtomicUsize, Ordering},
+	time::{Duration, SystemTime, UNIX_EPOCH},
 };
 
 use rand::{thread_rng, Rng};
 use semver::{Version, VersionReq};
 use serde_json::{json, Map, Value};
 
+// The data of a sticky hook, along with the trace it was triggered with
+type StickyHook = (Map<String, Value>, TraceContext);
+
+static NEXT_CONNECTION_INDEX: AtomicUsize = AtomicUsize::new(0);
+// Unique for every function call, unlike the time it was made at
+static NEXT_CALL_ID: AtomicU64 = AtomicU64::new(0);
+
 lazy_static! {
 	static ref REGISTERED_MODULES: RwLock<HashMap<String, Module>> = RwLock::new(HashMap::new());
 	static ref UNREGISTERED_MODULES: RwLock<HashMap<String, Module>> = RwLock::new(HashMap::new());
-	static ref REQUEST_ORIGINS: RwLock<HashMap<String, String>> = RwLock::new(HashMap::new());
+	static ref REQUEST_ORIGINS: RwLock<PendingCalls> = RwLock::new(PendingCalls::default());
+	// requestId -> callId of function calls that timed out, so that late responses can be dropped
+	static ref TIMED_OUT_REQUESTS: RwLock<HashMap<String, u64>> = RwLock::new(HashMap::new());
 	static ref MODULE_UUID_TO_ID: RwLock<HashMap<u128, String>> = RwLock::new(HashMap::new());
+	// The last data of every sticky hook, by the fully qualified name of the hook
+	static ref STICKY_HOOKS: RwLock<HashMap<String, StickyHook>> =
+		RwLock::new(HashMap::new());
+	static ref FUNCTION_CALL_TIMEOUT: RwLock<u64> =
+		RwLock::new(constants::DEFAULT_FUNCTION_CALL_TIMEOUT);
+	// connectionId -> resumption token of every registered connection
+	static ref RESUMPTION_TOKENS: RwLock<HashMap<u128, String>> = RwLock::new(HashMap::new());
+	// resumption token -> connections that disconnected, but are still in their grace period
+	static ref SUSPENDED_CONNECTIONS: RwLock<HashMap<String, SuspendedConnection>> =
+		RwLock::new(HashMap::new());
+	static ref DISCONNECT_GRACE_PERIOD: RwLock<u64> =
+		RwLock::new(constants::DEFAULT_DISCONNECT_GRACE_PERIOD);
+	// If there are no credentials, modules don't have to authenticate
+	static ref CREDENTIALS: RwLock<Option<Vec<Credential>>> = RwLock::new(None);
+	// connectionId -> number of failed attempts to authenticate
+	static ref AUTHENTICATION_FAILURES: RwLock<HashMap<u128, u32>> = RwLock::new(HashMap::new());
+	static ref MAX_AUTHENTICATION_FAILURES: RwLock<u32> =
+		RwLock::new(constants::DEFAULT_MAX_AUTHENTICATION_FAILURES);
+	// If there is no policy, all modules can call any function and listen for any hook
+	static ref POLICY: RwLock<Option<Policy>> = RwLock::new(None);
+	// The rate limits of modules that aren't overridden by the policy
+	static ref DEFAULT_RATE_LIMITS: RwLock<RateLimits> = RwLock::new(RateLimits::default());
+	// moduleId -> kind of rate limit -> usage. Replicas of a module share the same limits
+	static ref RATE_LIMITERS: RwLock<HashMap<String, HashMap<&'static str, TokenBucket>>> =
+		RwLock::new(HashMap::new());
+	// How many messages can be waiting to be written to a connection. 0 doesn't limit it
+	static ref QUEUE_LIMIT: RwLock<usize> = RwLock::new(constants::DEFAULT_QUEUE_LIMIT);
+	// What happens when a queue is full, for modules that don't choose for themselves
+	static ref DEFAULT_BACKPRESSURE: RwLock<Backpressure> = RwLock::new(Backpressure::DropOldest);
+	// In milliseconds. How often connections are pinged. 0 doesn't ping them at all
+	static ref HEARTBEAT_INTERVAL: RwLock<u64> = RwLock::new(0);
+	static ref MAX_MISSED_HEARTBEATS: RwLock<u32> =
+		RwLock::new(constants::DEFAULT_MAX_MISSED_HEARTBEATS);
+	// module_uuid -> how many pings in a row were left unanswered
+	static ref MISSED_HEARTBEATS: RwLock<HashMap<u128, u32>> = RwLock::new(HashMap::new());
 }
 
 pub async fn handle_request(module_comm: &ModuleComm, data: String) {
+	// Everything logged while handling the request is about this connection
+	let mut context = Fields::new().connection_id(module_comm.get_uuid());
+	if let Some(module_id) = get_module_id_for_uuid(module_comm.get_uuid()).await {
+		context = context.module_id(&module_id);
+	}
+	logger::in_context(context, process_request(module_comm, data)).await;
+}
+
+async fn process_request(module_comm: &ModuleComm, data: String) {
 	let json_result = serde_json::from_str(&data);
 	logger::verbose("Got request. Processing...");
 
 	if json_result.is_err() {
 		logger::warn("Request is not parsable. Ignoring...");
+		metrics::record_request(module_comm.get_uuid(), None);
 		return;
 	}
 
@@ -39,52 +107,162 @@ pub async fn handle_request(module_comm: &ModuleComm, data: String) {
 	if r#type == None {
 		logger::warn("type not present. Sending error...");
 		send_error(module_comm, "undefined", errors::UNKNOWN_REQUEST).await;
+		metrics::record_request(module_comm.get_uuid(), None);
 		return;
 	}
 	let r#type = r#type.unwrap();
 	if request_id == None {
 		logger::warn("requestId not present. Sending error...");
 		send_error(module_comm, "undefined", errors::INVALID_REQUEST_ID).await;
+		metrics::record_request(module_comm.get_uuid(), Some(r#type));
 		return;
 	}
 	let request_id = request_id.unwrap();
 
+	logger::in_context(
+		Fields::new().request_id(request_id),
+		dispatch_request(module_comm, r#type, request_id, &input, data.len()),
+	)
+	.await;
+}
+
+async fn dispatch_request(
+	module_comm: &ModuleComm,
+	r#type: u64,
+	request_id: &str,
+	input: &Value,
+	size: usize,
+) {
+	if let Some((kind, retry_after)) = check_rate_limits(module_comm, r#type, size).await {
+		logger::warn!(
+			"Connection {} is over its '{}' rate limit. Sending error...",
+			module_comm.get_uuid(),
+			kind
+		);
+		send_rate_limited_error(module_comm, request_id, kind, retry_after).await;
+		metrics::record_request(module_comm.get_uuid(), Some(r#type));
+		return;
+	}
+
 	match r#type {
 		request_types::REGISTER_MODULE_REQUEST => {
 			logger::verbose("Processing request as module registration...");
-			handle_module_registration(module_comm, request_id, &input).await;
+			handle_module_registration(module_comm, request_id, input).await;
 		}
 		request_types::DECLARE_FUNCTION_REQUEST => {
 			logger::verbose("Processing request as declare function...");
-			handle_declare_function(module_comm, request_id, &input).await;
+			handle_declare_function(module_comm, request_id, input).await;
 		}
 		request_types::FUNCTION_CALL_REQUEST => {
 			logger::verbose("Processing request as function call...");
-			handle_function_call(module_comm, request_id, &input).await;
+			handle_function_call(module_comm, request_id, input).await;
 		}
 		request_types::FUNCTION_CALL_RESPONSE => {
 			logger::verbose("Processing request as function response...");
-			handle_function_response(module_comm, request_id, &input).await;
+			handle_function_response(module_comm, request_id, input).await;
 		}
 		request_types::REGISTER_HOOK_REQUEST => {
 			logger::verbose("Processing request as register hook...");
-			handle_register_hook(module_comm, request_id, &input).await;
+			handle_register_hook(module_comm, request_id, input).await;
 		}
 		request_types::TRIGGER_HOOK_REQUEST => {
 			logger::verbose("Processing request as trigger hook...");
-			handle_trigger_hook(module_comm, request_id, &input).await;
+			handle_trigger_hook(module_comm, request_id, input).await;
+		}
+		request_types::UNREGISTER_HOOK_REQUEST => {
+			logger::verbose("Processing request as unregister hook...");
+			handle_unregister_hook(module_comm, request_id, input).await;
+		}
+		request_types::UNDECLARE_FUNCTION_REQUEST => {
+			logger::verbose("Processing request as undeclare function...");
+			handle_undeclare_function(module_comm, request_id, input).await;
+		}
+		request_types::PING => {
+			logger::verbose("Processing request as ping...");
+			handle_ping(module_comm, request_id).await;
+		}
+		request_types::PONG => {
+			logger::verbose("Processing request as pong...");
+			handle_pong(module_comm).await;
 		}
 		_ => {
-			logger::debug(&format!(
-				"Found unknown request type {}. Sending error...",
-				r#type
-			));
+			logger::debug!("Found unknown request type {}. Sending error...", r#type);
 			send_error(module_comm, request_id, errors::UNKNOWN_REQUEST).await;
 		}
 	}
+	metrics::record_request(module_comm.get_uuid(), Some(r#type));
 	logger::verbose("Completed processing the request");
 }
 
+pub async fn get_rate_limit_usage(module_id: &str) -> Value {
+	let policy = POLICY.read().await;
+	let default_rate_limits = DEFAULT_RATE_LIMITS.read().await;
+	let mut rate_limiters = RATE_LIMITERS.write().await;
+
+	let mut usage = Map::new();
+	for kind in [
+		rate_limits::CALLS,
+		rate_limits::TRIGGERS,
+		rate_limits::BYTES,
+	]
+	.iter()
+	{
+		let limit = get_rate_limit(&policy, &default_rate_limits, module_id, kind);
+		let bucket = rate_limiters
+			.get_mut(module_id)
+			.and_then(|buckets| buckets.get_mut(kind));
+		let (remaining, rejected) = match bucket {
+			Some(bucket) => (bucket.get_remaining(limit), bucket.get_rejected()),
+			None => (limit, 0),
+		};
+		usage.insert(
+			String::from(*kind),
+			json!({
+				request_keys::LIMIT: limit,
+				"remaining": if limit == 0 { Value::Null } else { json!(remaining) },
+				"rejected": rejected
+			}),
+		);
+	}
+	Value::Object(usage)
+}
+
+// Renders the metrics, along with the current state of every module
+pub async fn get_metrics() -> String {
+	let registered_modules = REGISTERED_MODULES.read().await;
+	let unregistered_modules = UNREGISTERED_MODULES.read().await;
+	let modules = registered_modules
+		.values()
+		.chain(unregistered_modules.values());
+	let gauges = metrics::Gauges {
+		registered_modules: registered_modules.len(),
+		unregistered_modules: unregistered_modules.len(),
+		queue_depths: modules
+			.clone()
+			.flat_map(|module| {
+				module
+					.get_queue_depths()
+					.into_iter()
+					.map(move |(connection_uuid, depth)| {
+						(module.get_module_id().clone(), connection_uuid, depth)
+					})
+			})
+			.collect(),
+		dropped_messages: modules
+			.map(|module| {
+				(
+					module.get_module_id().clone(),
+					module.get_dropped_message_count(),
+				)
+			})
+			.collect(),
+	};
+	drop(registered_modules);
+	drop(unregistered_modules);
+
+	metrics::render(&gauges)
+}
+
 pub async fn get_registered_modules() -> Vec<Module> {
 	let registered_modules = REGISTERED_MODULES.read().await;
 	let mut modules = vec![];
@@ -123,28 +301,84 @@ pub async fn get_module_by_id(module_id: &str) -> Option<Module> {
 	None
 }
 
+pub async fn get_dependency_status(module_id: &str) -> Option<Value> {
+	let registered_modules = REGISTERED_MODULES.read().await;
+	let unregistered_modules = UNREGISTERED_MODULES.read().await;
+	if !registered_modules.contains_key(module_id) && !unregistered_modules.contains_key(module_id)
+	{
+		return None;
+	}
+
+	let unmet_dependencies = get_all_unmet_dependencies(&registered_modules, &unregistered_modules)
+		.remove(module_id)
+		.unwrap_or_default();
+	let mut status = get_dependency_status_object(
+		registered_modules.contains_key(module_id),
+		&unmet_dependencies,
+	);
+	status[request_keys::MODULE_ID] = Value::String(String::from(module_id));
+	Some(status)
+}
+
 pub async fn on_module_disconnected(module_comm: &ModuleComm) {
-	logger::verbose(&format!(
+	let module_uuid = module_comm.get_uuid();
+	AUTHENTICATION_FAILURES.write().await.remove(module_uuid);
+	let resumption_token = RESUMPTION_TOKENS.write().await.remove(module_uuid);
+	let grace_period = *DISCONNECT_GRACE_PERIOD.read().await;
+
+	if let Some(resumption_token) = resumption_token {
+		if grace_period > 0 && suspend_connection(module_uuid, &resumption_token).await {
+			logger::info_with!(
+				Fields::new().connection_id(module_uuid),
+				"Connection {} will be kept for {} milliseconds for it to be resumed",
+				module_uuid,
+				grace_period
+			);
+			task::spawn(expire_suspended_connection(resumption_token, grace_period));
+			return;
+		}
+	}
+
+	disconnect_module(module_uuid).await;
+}
+
+async fn disconnect_module(module_uuid: &u128) {
+	logger::verbose!(
 		"Module with UUID {} disconnected. Processing...",
-		module_comm.get_uuid()
-	));
+		module_uuid
+	);
 	// recheck dependencies
-	let module_id = get_module_id_for_uuid(&module_comm.get_uuid()).await;
+	let module_id = get_module_id_for_uuid(module_uuid).await;
 
 	if module_id.is_none() {
 		logger::verbose("Module does not have a moduleId. No more processing required");
 		return;
 	}
 	let module_id = module_id.unwrap();
-	logger::verbose(&format!(
-		"Module is associated with moduleId '{}'",
-		module_id
-	));
+	logger::verbose!("Module is associated with moduleId '{}'", module_id);
+
+	MODULE_UUID_TO_ID.write().await.remove(module_uuid);
 
 	let mut registered_modules = REGISTERED_MODULES.write().await;
 	let mut unregistered_modules = UNREGISTERED_MODULES.write().await;
 
-	if registered_modules.contains_key(&module_id) {
+	let activated_version = registered_modules
+		.get(&module_id)
+		.map(|module| module.get_version().clone());
+	let module = registered_modules
+		.get_mut(&module_id)
+		.or_else(|| unregistered_modules.get_mut(&module_id));
+	// Either a replica, or the previous version of an upgraded module. The module is still available
+	let is_replica = module.is_some() && {
+		let module = module.as_ref().unwrap();
+		module.is_connection_draining(module_uuid) || module.get_active_connection_uuids().len() > 1
+	};
+
+	if is_replica {
+		logger::verbose("Module has other replicas connected. Removing only this connection...");
+		module.unwrap().remove_connection(module_uuid);
+		logger::verbose("Connection removed from module");
+	} else if registered_modules.contains_key(&module_id) {
 		logger::verbose("Module is a registered module. Removing...");
 		registered_modules
 			.remove(&module_id)
@@ -163,15 +397,206 @@ pub async fn on_module_disconnected(module_comm: &ModuleComm) {
 	}
 	drop(registered_modules);
 	drop(unregistered_modules);
-	logger::info(&format!("Module '{}' disconnected.", module_id));
 
-	recalculate_all_module_dependencies().await;
+	fail_pending_function_calls(module_uuid).await;
+
+	if is_replica {
+		logger::info_with!(
+			Fields::new()
+				.module_id(&module_id)
+				.connection_id(module_uuid),
+			"A replica of module '{}' disconnected. The module is still available",
+			module_id
+		);
+	} else {
+		logger::info_with!(
+			Fields::new()
+				.module_id(&module_id)
+				.connection_id(module_uuid),
+			"Module '{}' disconnected.",
+			module_id
+		);
+		RATE_LIMITERS.write().await.remove(&module_id);
+		// Hooks the module stickied shouldn't outlive it
+		let hook_prefix = module_id.clone() + ".";
+		STICKY_HOOKS
+			.write()
+			.await
+			.retain(|hook_name, _| !hook_name.starts_with(&hook_prefix));
+		recalculate_all_module_dependencies().await;
+		if let Some(version) = activated_version {
+			notify_optional_dependents(&module_id, &version, false).await;
+		}
+	}
 
-	// Trigger a hook about the module being disconnected
-	logger::verbose(&format!(
-		"Triggerring hook about connectionId '{}' disconnection",
+	// The module is still connected through its other replicas
+	if !is_replica {
+		// Trigger a hook about the module being disconnected
+		logger::verbose!(
+			"Triggerring hook about connectionId '{}' disconnection",
+			module_uuid
+		);
+		trigger_hook(
+			&REGISTERED_MODULES
+				.read()
+				.await
+				.get(constants::APP_NAME)
+				.unwrap()
+				.clone(),
+			constants::juno_hooks::MODULE_DISCONNECTED,
+			json!({ request_keys::CONNECTION_ID: module_uuid.to_string() })
+				.as_object()
+				.unwrap(),
+			false,
+			false,
+		)
+		.await;
+	}
+
+	logger::verbose("Module is no longer tracked");
+}
+
+// Keeps the module of a disconnected connection around, queueing everything sent to it,
+// so that the connection can be resumed. Returns false if the connection can't be resumed
+async fn suspend_connection(module_uuid: &u128, resumption_token: &str) -> bool {
+	let module_id = get_module_id_for_uuid(module_uuid).await;
+	if module_id.is_none() {
+		return false;
+	}
+	let module_id = module_id.unwrap();
+
+	let mut registered_modules = REGISTERED_MODULES.write().await;
+	let mut unregistered_modules = UNREGISTERED_MODULES.write().await;
+	let module = registered_modules
+		.get_mut(&module_id)
+		.or_else(|| unregistered_modules.get_mut(&module_id));
+	if module.is_none() || module.as_ref().unwrap().is_connection_draining(module_uuid) {
+		// The previous version of an upgraded module is on its way out anyway
+		return false;
+	}
+
+	logger::verbose!(
+		"Suspending connection {} of module '{}'...",
+		module_uuid,
+		module_id
+	);
+	// Nothing reads this queue until the connection is resumed, so it has no limit to wait on.
+	// It only grows for as long as the grace period
+	let (sender, receiver) = outbound_queue::channel(0, Backpressure::DropOldest);
+	module.unwrap().suspend_connection(module_uuid, sender);
+	SUSPENDED_CONNECTIONS.write().await.insert(
+		String::from(resumption_token),
+		SuspendedConnection::new(module_id, *module_uuid, receiver),
+	);
+	logger::verbose("Connection suspended");
+	true
+}
+
+async fn expire_suspended_connection(resumption_token: String, grace_period: u64) {
+	task::sleep(Duration::from_millis(grace_period)).await;
+
+	let suspended_connection = SUSPENDED_CONNECTIONS
+		.write()
+		.await
+		.remove(&resumption_token);
+	if let Some(suspended_connection) = suspended_connection {
+		logger::info_with!(
+			Fields::new()
+				.module_id(suspended_connection.get_module_id())
+				.connection_id(suspended_connection.get_module_uuid()),
+			"Connection {} of module '{}' was not resumed in time",
+			suspended_connection.get_module_uuid(),
+			suspended_connection.get_module_id()
+		);
+		disconnect_module(suspended_connection.get_module_uuid()).await;
+	}
+}
+
+async fn handle_module_resumption(
+	module_comm: &ModuleComm,
+	request_id: &str,
+	resumption_token: &str,
+) {
+	let suspended_connection = SUSPENDED_CONNECTIONS.write().await.remove(resumption_token);
+	if suspended_connection.is_none() {
+		logger::debug("The resumption token is invalid or has expired. Sending error...");
+		send_error(module_comm, request_id, errors::INVALID_RESUMPTION_TOKEN).await;
+		return;
+	}
+	let SuspendedConnection {
+		module_id,
+		module_uuid,
+		mut queued_messages,
+	} = suspended_connection.unwrap();
+
+	let mut module_uuid_to_id = MODULE_UUID_TO_ID.write().await;
+	if module_uuid_to_id.contains_key(module_comm.get_uuid()) {
+		drop(module_uuid_to_id);
+		logger::debug("A moduleId for that UUID already exists. This looks like a duplicate module. Sending error...");
+		send_error(module_comm, request_id, errors::DUPLICATE_MODULE).await;
+		// Put the connection back, for it to be resumed by another connection
+		SUSPENDED_CONNECTIONS.write().await.insert(
+			String::from(resumption_token),
+			SuspendedConnection::new(module_id, module_uuid, queued_messages),
+		);
+		return;
+	}
+	logger::info_with!(
+		Fields::new()
+			.module_id(&module_id)
+			.connection_id(module_comm.get_uuid()),
+		"Resuming connection {} of module '{}' as connection {}...",
+		module_uuid,
+		module_id,
 		module_comm.get_uuid()
-	));
+	);
+	module_uuid_to_id.remove(&module_uuid);
+	module_uuid_to_id.insert(*module_comm.get_uuid(), module_id.clone());
+	drop(module_uuid_to_id);
+
+	let mut registered_modules = REGISTERED_MODULES.write().await;
+	let mut unregistered_modules = UNREGISTERED_MODULES.write().await;
+	let module = registered_modules
+		.get_mut(&module_id)
+		.or_else(|| unregistered_modules.get_mut(&module_id))
+		.unwrap();
+	module.replace_connection(
+		&module_uuid,
+		*module_comm.get_uuid(),
+		module_comm.clone_sender(),
+	);
+	let activated = module.is_registered();
+
+	logger::verbose("Moving pending function calls over to the new connection...");
+	REQUEST_ORIGINS
+		.write()
+		.await
+		.move_connection(&module_uuid, *module_comm.get_uuid());
+
+	let unmet_dependencies = get_all_unmet_dependencies(&registered_modules, &unregistered_modules)
+		.remove(&module_id)
+		.unwrap_or_default();
+	drop(registered_modules);
+	drop(unregistered_modules);
+
+	logger::verbose("Notifying successful module resumption...");
+	let mut response = get_registration_response(
+		request_id,
+		activated,
+		&unmet_dependencies,
+		&issue_resumption_token(module_comm.get_uuid()).await,
+	);
+	response[request_keys::MODULE_ID] = Value::String(module_id);
+	send_module_comm(module_comm, &response).await;
+	logger::verbose("Notification successful");
+
+	logger::verbose("Sending all messages queued while disconnected...");
+	while let Some(message) = queued_messages.try_next() {
+		module_comm.send(message).await;
+	}
+	logger::verbose("All queued messages sent");
+
+	// Trigger a hook about the previous connection being gone for good
 	trigger_hook(
 		&REGISTERED_MODULES
 			.read()
@@ -180,15 +605,127 @@ pub async fn on_module_disconnected(module_comm: &ModuleComm) {
 			.unwrap()
 			.clone(),
 		constants::juno_hooks::MODULE_DISCONNECTED,
-		json!({ request_keys::CONNECTION_ID: module_comm.get_uuid().to_string() })
+		json!({ request_keys::CONNECTION_ID: module_uuid.to_string() })
 			.as_object()
 			.unwrap(),
 		false,
 		false,
 	)
 	.await;
+}
 
-	logger::verbose("Module is no longer tracked");
+async fn issue_resumption_token(module_uuid: &u128) -> String {
+	let resumption_token = format!("{:032x}", thread_rng().gen::<u128>());
+	RESUMPTION_TOKENS
+		.write()
+		.await
+		.insert(*module_uuid, resumption_token.clone());
+	resumption_token
+}
+
+async fn fail_pending_function_calls(module_uuid: &u128) {
+	logger::verbose!(
+		"Failing all pending function calls to and from connectionId '{}'...",
+		module_uuid
+	);
+	let mut request_origins = REQUEST_ORIGINS.write().await;
+
+	// Calls made by the disconnected module can never be responded to. Purge them
+	request_origins.retain(|origin| {
+		if origin.get_module_uuid() == module_uuid {
+			origin.get_span().finish("the caller disconnected");
+			return false;
+		}
+		true
+	});
+
+	// Calls made to the disconnected module will never get a response. Fail them
+	let failed_calls: Vec<(String, RequestOrigin)> = request_origins
+		.iter()
+		.filter(|(_, origin)| origin.get_target_module_uuid() == module_uuid)
+		.map(|(request_id, origin)| (request_id.clone(), origin.clone()))
+		.collect();
+	for (request_id, origin) in failed_calls.iter() {
+		request_origins.remove(request_id);
+		origin.get_span().finish("the target module disconnected");
+	}
+	drop(request_origins);
+
+	let registered_modules = REGISTERED_MODULES.read().await;
+	let failed_calls: Vec<(String, RequestOrigin, Option<Module>)> = failed_calls
+		.into_iter()
+		.map(|(request_id, origin)| {
+			let origin_module = registered_modules.get(origin.get_module_id()).cloned();
+			(request_id, origin, origin_module)
+		})
+		.collect();
+	drop(registered_modules);
+
+	for (request_id, origin, origin_module) in failed_calls {
+		logger::debug!("The function call with requestId '{}' from module '{}' will not be responded to. Sending error...",
+			request_id,
+			origin.get_module_id());
+		if let Some(origin_module) = origin_module {
+			send_module_error(
+				&origin_module,
+				origin.get_module_uuid(),
+				&request_id,
+				errors::MODULE_DISCONNECTED,
+			)
+			.await;
+		}
+	}
+	logger::verbose("All pending function calls have been failed");
+
+	close_drained_connections().await;
+}
+
+pub async fn set_function_call_timeout(timeout: u64) {
+	*FUNCTION_CALL_TIMEOUT.write().await = timeout;
+}
+
+pub async fn set_disconnect_grace_period(grace_period: u64) {
+	*DISCONNECT_GRACE_PERIOD.write().await = grace_period;
+}
+
+pub async fn set_credentials(credentials: Vec<Credential>) {
+	*CREDENTIALS.write().await = Some(credentials);
+}
+
+pub async fn set_max_authentication_failures(max_failures: u32) {
+	*MAX_AUTHENTICATION_FAILURES.write().await = max_failures;
+}
+
+pub async fn set_policy(policy: Policy) {
+	*POLICY.write().await = Some(policy);
+}
+
+pub async fn set_default_rate_limits(rate_limits: RateLimits) {
+	*DEFAULT_RATE_LIMITS.write().await = rate_limits;
+}
+
+pub async fn set_queue_limit(queue_limit: usize) {
+	*QUEUE_LIMIT.write().await = queue_limit;
+}
+
+pub async fn set_default_backpressure(backpressure: Backpressure) {
+	*DEFAULT_BACKPRESSURE.write().await = backpressure;
+}
+
+pub async fn set_heartbeat_interval(heartbeat_interval: u64) {
+	*HEARTBEAT_INTERVAL.write().await = heartbeat_interval;
+}
+
+pub async fn set_max_missed_heartbeats(max_missed_heartbeats: u32) {
+	*MAX_MISSED_HEARTBEATS.write().await = max_missed_heartbeats;
+}
+
+// Creates the queue of data waiting to be written to a new connection
+pub async fn new_outbound_queue() -> (OutboundSender, OutboundReceiver) {
+	outbound_queue::channel(
+		*QUEUE_LIMIT.read().await,
+		*DEFAULT_BACKPRESSURE.read().await,
+	)
 }
 
 pub async fn new_connection_id() -> u128 {
@@ -198,12 +735,13 @@ pub async fn new_connection_id() -> u128 {
 	while uuid == 0 || MODULE_UUID_TO_ID.read().await.contains_key(&uuid) {
 		uuid = thread_rng().gen();
 	}
+	metrics::on_connection_opened();
 
 	// Trigger a hook about the module being connected
-	logger::verbose(&format!(
+	logger::verbose!(
 		"Triggerring hook about new connectionId '{}' generation",
 		uuid
-	));
+	);
 	let juno_module = REGISTERED_MODULES
 		.read()
 		.await
@@ -224,10 +762,118 @@ pub async fn new_connection_id() -> u128 {
 	uuid
 }
 
+// Pings the connection every heartbeat interval, for as long as it is connected. If it leaves too
+// many pings in a row unanswered, it's most likely dead (but not closed), so it is closed instead
+pub async fn heartbeat_loop(module_comm: &ModuleComm) {
+	let heartbeat_interval = *HEARTBEAT_INTERVAL.read().await;
+	if heartbeat_interval == 0 || module_comm.is_internal() {
+		return;
+	}
+	let max_missed_heartbeats = *MAX_MISSED_HEARTBEATS.read().await;
+	let module_uuid = *module_comm.get_uuid();
+
+	loop {
+		task::sleep(Duration::from_millis(heartbeat_interval)).await;
+
+		let mut missed_heartbeats = MISSED_HEARTBEATS.write().await;
+		let missed = missed_heartbeats.entry(module_uuid).or_insert(0);
+		if *missed >= max_missed_heartbeats {
+			logger::warn_with!(
+				Fields::new().connection_id(&module_uuid),
+				"Connection {} did not respond to {} pings in a row. Closing connection...",
+				module_uuid,
+				missed
+			);
+			missed_heartbeats.remove(&module_uuid);
+			drop(missed_heartbeats);
+			// Closing the sending queue shuts the socket down, which disconnects the module as usual
+			module_comm.close_sender().await;
+			return;
+		}
+		*missed += 1;
+		drop(missed_heartbeats);
+
+		logger::verbose!("Pinging connection {}...", module_uuid);
+		// The ping isn't waited on, so that a connection that isn't reading can't hold up the
+		// check above. If there's no room for it, it counts as a missed heartbeat all the same
+		let ping = json!({
+			request_keys::REQUEST_ID: generate_request_id().await,
+			request_keys::TYPE: request_types::PING
+		});
+		if let Err(error) = module_comm.get_sender().try_send(ping.to_string() + "\n") {
+			logger::verbose!("Unable to ping connection {}: {}", module_uuid, error);
+		}
+	}
+}
+
+// Forgets about a connection that is gone, once it has been disconnected
+pub async fn on_connection_closed(module_uuid: &u128) {
+	MISSED_HEARTBEATS.write().await.remove(module_uuid);
+	metrics::on_connection_closed(module_uuid);
+}
+
+async fn handle_ping(module_comm: &ModuleComm, request_id: &str) {
+	send_module_comm(
+		module_comm,
+		&json!({
+			request_keys::REQUEST_ID: request_id,
+			request_keys::TYPE: request_types::PONG
+		}),
+	)
+	.await;
+}
+
+async fn handle_pong(module_comm: &ModuleComm) {
+	if let Some(missed) = MISSED_HEARTBEATS
+		.write()
+		.await
+		.get_mut(module_comm.get_uuid())
+	{
+		*missed = 0;
+	}
+}
+
 async fn handle_module_registration(module_comm: &ModuleComm, request_id: &str, request: &Value) {
+	if let Some(resumption_token) = request[request_keys::RESUMPTION_TOKEN].as_str() {
+		logger::verbose("Request has a resumption token. Resuming the module's session...");
+		handle_module_resumption(module_comm, request_id, resumption_token).await;
+		return;
+	}
+
 	let module_id = request[request_keys::MODULE_ID].as_str();
 	let version = request[request_keys::VERSION].as_str();
 	let dependencies = request[request_keys::DEPENDENCIES].as_object();
+	let optional_dependencies = request[request_keys::OPTIONAL_DEPENDENCIES].as_object();
+	let replica = if request[request_keys::REPLICA].is_null() {
+		false
+	} else if let Some(replica) = request[request_keys::REPLICA].as_bool() {
+		replica
+	} else {
+		logger::debug("replica is not a boolean. Sending error...");
+		send_error(module_comm, request_id, errors::MALFORMED_REQUEST).await;
+		return;
+	};
+	let upgrade = if request[request_keys::UPGRADE].is_null() {
+		false
+	} else if let Some(upgrade) = request[request_keys::UPGRADE].as_bool() {
+		upgrade
+	} else {
+		logger::debug("upgrade is not a boolean. Sending error...");
+		send_error(module_comm, request_id, errors::MALFORMED_REQUEST).await;
+		return;
+	};
+	let backpressure = if request[request_keys::BACKPRESSURE].is_null() {
+		*DEFAULT_BACKPRESSURE.read().await
+	} else if let Some(backpressure) = request[request_keys::BACKPRESSURE]
+		.as_str()
+		.and_then(Backpressure::parse)
+	{
+		backpressure
+	} else {
+		logger::debug("backpressure is not a known backpressure policy. Sending error...");
+		send_error(module_comm, request_id, errors::MALFORMED_REQUEST).await;
+		return;
+	};
 
 	if module_id == None {
 		logger::debug("moduleId not present. Sending error...");
@@ -243,36 +889,38 @@ async fn handle_module_registration(module_comm: &ModuleComm, request_id: &str,
 	}
 	let version = version.unwrap();
 
-	let mut dependency_map = HashMap::new();
+	if !is_authenticated(module_comm, module_id, request).await {
+		logger::warn_with!(
+			Fields::new()
+				.module_id(module_id)
+				.connection_id(module_comm.get_uuid())
+				.request_id(request_id),
+			"Connection {} failed to authenticate as module '{}'. Sending error...",
+			module_comm.get_uuid(),
+			module_id
+		);
+		send_error(module_comm, request_id, errors::UNAUTHORIZED).await;
+		on_authentication_failure(module_comm).await;
+		return;
+	}
+	AUTHENTICATION_FAILURES
+		.write()
+		.await
+		.remove(module_comm.get_uuid());
 
-	// If dependencies are not null, then populate the dependency_map
-	if let Some(dependencies) = dependencies {
-		logger::verbose("Dependency is not null. Populating hashmap...");
-		for dependency in dependencies.keys() {
-			if !dependencies[dependency].is_string() {
-				logger::debug(&format!(
-					"Dependency value for key {} is not a string. Sending error...",
-					dependency
-				));
-				send_error(module_comm, request_id, errors::MALFORMED_REQUEST).await;
-				return;
-			}
-			let dependency_requirement =
-				VersionReq::parse(dependencies[dependency].as_str().unwrap());
-			if dependency_requirement.is_err() {
-				logger::debug(&format!("Dependency value for key {} is not a valid SemVer version requirement. Sending error...", dependency));
-				send_error(module_comm, request_id, errors::MALFORMED_REQUEST).await;
-				return;
-			}
-			dependency_map.insert(dependency.clone(), dependency_requirement.unwrap());
-		}
-		logger::verbose(&format!(
-			"HashMap populated with {} dependencies",
-			dependency_map.len()
-		));
-	} else {
-		logger::verbose("Dependency is null. Pre-assigning a new HashMap");
+	let dependency_map = get_dependency_map(dependencies);
+	if dependency_map.is_none() {
+		send_error(module_comm, request_id, errors::MALFORMED_REQUEST).await;
+		return;
+	}
+	let dependency_map = dependency_map.unwrap();
+
+	let optional_dependency_map = get_dependency_map(optional_dependencies);
+	if optional_dependency_map.is_none() {
+		send_error(module_comm, request_id, errors::MALFORMED_REQUEST).await;
+		return;
 	}
+	let optional_dependency_map = optional_dependency_map.unwrap();
 
 	let version = Version::parse(version);
 	if version.is_err() {
@@ -282,10 +930,15 @@ async fn handle_module_registration(module_comm: &ModuleComm, request_id: &str,
 	}
 	let version = version.unwrap();
 
-	logger::info(&format!(
+	logger::info_with!(
+		Fields::new()
+			.module_id(module_id)
+			.connection_id(module_comm.get_uuid())
+			.request_id(request_id),
 		"Registering module '{}' with version '{}'...",
-		module_id, version
-	));
+		module_id,
+		version
+	);
 	let mut module = Module::new(
 		*module_comm.get_uuid(),
 		String::from(module_id),
@@ -293,14 +946,45 @@ async fn handle_module_registration(module_comm: &ModuleComm, request_id: &str,
 		module_comm.clone_sender(),
 	);
 	module.set_dependencies(dependency_map);
+	module.set_optional_dependencies(optional_dependency_map);
+	module.set_replicable(replica);
+	module.set_backpressure(backpressure);
 
 	let mut registered_modules = REGISTERED_MODULES.write().await;
 	let mut unregistered_modules = UNREGISTERED_MODULES.write().await;
 
-	if registered_modules.contains_key(module_id) || unregistered_modules.contains_key(module_id) {
-		logger::debug("Either registered modules or unregistered modules already has this moduleId. Sending error...");
-		send_error(module_comm, request_id, errors::DUPLICATE_MODULE).await;
-		return;
+	// Upgrades replace the existing module instead, and are checked further below
+	let existing_module = registered_modules
+		.get(module_id)
+		.or_else(|| unregistered_modules.get(module_id))
+		.filter(|_| !upgrade);
+	if let Some(existing_module) = existing_module {
+		if !replica || !existing_module.is_replicable() {
+			logger::debug("Either registered modules or unregistered modules already has this moduleId. Sending error...");
+			drop(registered_modules);
+			drop(unregistered_modules);
+			send_error(module_comm, request_id, errors::DUPLICATE_MODULE).await;
+			return;
+		}
+		if existing_module.get_version() != module.get_version() {
+			logger::debug!("A replica must have the same version as the module it replicates. Required '{}', present '{}'. Sending error...",
+				existing_module.get_version(),
+				module.get_version());
+			drop(registered_modules);
+			drop(unregistered_modules);
+			send_error(module_comm, request_id, errors::DUPLICATE_MODULE).await;
+			return;
+		}
+		// The module is activated (or not) for all of its replicas at once
+		if existing_module.get_dependencies() != module.get_dependencies() ||
+			existing_module.get_optional_dependencies() != module.get_optional_dependencies()
+		{
+			logger::debug("A replica must have the same dependencies as the module it replicates. Sending error...");
+			drop(registered_modules);
+			drop(unregistered_modules);
+			send_error(module_comm, request_id, errors::DUPLICATE_MODULE).await;
+			return;
+		}
 	}
 
 	// Register that this uuid belongs to this moduleId
@@ -308,24 +992,120 @@ async fn handle_module_registration(module_comm: &ModuleComm, request_id: &str,
 	let mut module_uuid_to_id = MODULE_UUID_TO_ID.write().await;
 	if module_uuid_to_id.contains_key(module_comm.get_uuid()) {
 		logger::debug("A moduleId for that UUID already exists. This looks like a duplicate module. Sending error...");
+		drop(module_uuid_to_id);
+		drop(registered_modules);
+		drop(unregistered_modules);
 		send_error(module_comm, request_id, errors::DUPLICATE_MODULE).await;
 		return;
 	}
 	module_uuid_to_id.insert(*module_comm.get_uuid(), String::from(module_id));
 	drop(module_uuid_to_id);
 
-	logger::verbose("Notifying successful module registration...");
-	send_module(
-		&module,
-		&json!({
-			request_keys::REQUEST_ID: request_id,
-			request_keys::TYPE: request_types::REGISTER_MODULE_RESPONSE
-		}),
-	)
-	.await;
+	if upgrade &&
+		(registered_modules.contains_key(module_id) ||
+			unregistered_modules.contains_key(module_id))
+	{
+		handle_module_upgrade(
+			module_comm,
+			request_id,
+			module,
+			registered_modules,
+			unregistered_modules,
+		)
+		.await;
+		return;
+	}
+
+	let existing_module = registered_modules
+		.get_mut(module_id)
+		.or_else(|| unregistered_modules.get_mut(module_id));
+	if let Some(existing_module) = existing_module {
+		logger::info_with!(
+			Fields::new()
+				.module_id(module_id)
+				.connection_id(module_comm.get_uuid())
+				.request_id(request_id),
+			"Connection {} is being added as a replica of module '{}'",
+			module_comm.get_uuid(),
+			module_id
+		);
+		existing_module.add_connection(*module_comm.get_uuid(), module_comm.clone_sender());
+		let activated = existing_module.is_registered();
+
+		let unmet_dependencies =
+			get_all_unmet_dependencies(&registered_modules, &unregistered_modules)
+				.remove(module_id)
+				.unwrap_or_default();
+
+		// The replica can be called as soon as the modules are unlocked, so the response (and
+		// the activation hook) have to be queued before that. Like forced hooks, they can't
+		// wait on a full queue while the modules are locked
+		logger::verbose("Notifying successful module registration...");
+		force_send_module_comm(
+			module_comm,
+			&get_registration_response(
+				request_id,
+				activated,
+				&unmet_dependencies,
+				&issue_resumption_token(module_comm.get_uuid()).await,
+			),
+		);
+		logger::verbose("Notification successful");
+
+		if activated {
+			// The rest of the replicas have already been activated. Let this one know too
+			logger::verbose("Triggering activation hook on the replica...");
+			force_send_module_comm(
+				module_comm,
+				&json!({
+					request_keys::REQUEST_ID: generate_request_id().await,
+					request_keys::TYPE: request_types::TRIGGER_HOOK_RESPONSE,
+					request_keys::HOOK: format!("{}.{}", constants::APP_NAME, juno_hooks::ACTIVATED),
+					request_keys::DATA: Map::new()
+				}),
+			);
+			logger::verbose("Activation hook triggered");
+		}
+		return;
+	}
+
+	let unmet_dependencies = {
+		let mut modules: HashMap<&String, &Module> = registered_modules
+			.iter()
+			.chain(unregistered_modules.iter())
+			.collect();
+		modules.insert(module.get_module_id(), &module);
+		dependency_graph::get_unmet_dependencies(&modules)
+			.remove(module_id)
+			.unwrap_or_default()
+	};
+	if !unmet_dependencies.is_empty() {
+		logger::info_with!(
+			Fields::new().module_id(module_id),
+			"Module '{}' can't be activated yet. It has {} unmet dependencies",
+			module_id,
+			unmet_dependencies.len()
+		);
+		for unmet_dependency in unmet_dependencies.iter() {
+			logger::debug(&unmet_dependency.get_description());
+		}
+	}
+
+	// The response has to be queued before the activation hook, which is forced while the
+	// modules are locked. So it can't wait on a full queue either
+	logger::verbose("Notifying successful module registration...");
+	force_send_module(
+		&module,
+		&get_registration_response(
+			request_id,
+			unmet_dependencies.is_empty(),
+			&unmet_dependencies,
+			&issue_resumption_token(module_comm.get_uuid()).await,
+		),
+	);
 	logger::verbose("Notification successful");
 
-	if module.get_dependencies().is_empty() {
+	if module.get_dependencies().is_empty() && unmet_dependencies.is_empty() {
 		module.set_registered(true);
 
 		logger::verbose("Triggering activation hook...");
@@ -339,7 +1119,10 @@ async fn handle_module_registration(module_comm: &ModuleComm, request_id: &str,
 		.await;
 		logger::verbose("Activation hook triggered");
 
+		notify_of_optional_dependencies(&module, &registered_modules, &[]).await;
+
 		logger::verbose("Dependencies are none. Adding to registered modules...");
+		let version = module.get_version().clone();
 		registered_modules.insert(String::from(module_id), module);
 		logger::verbose("Module added to registered modules");
 
@@ -364,6 +1147,8 @@ async fn handle_module_registration(module_comm: &ModuleComm, request_id: &str,
 		)
 		.await;
 		logger::verbose("All modules notified of activated module");
+
+		notify_optional_dependents(module_id, &version, true).await;
 	} else {
 		module.set_registered(false);
 
@@ -378,6 +1163,371 @@ async fn handle_module_registration(module_comm: &ModuleComm, request_id: &str,
 	recalculate_all_module_dependencies().await;
 }
 
+// Replaces an existing module with a new version of it, without deactivating its dependents.
+// New function calls and hooks go to the successor, while the connections of the previous
+// version are closed once they have responded to the function calls that are still pending
+async fn handle_module_upgrade(
+	module_comm: &ModuleComm,
+	request_id: &str,
+	successor: Module,
+	mut registered_modules: RwLockWriteGuard<'_, HashMap<String, Module>>,
+	mut unregistered_modules: RwLockWriteGuard<'_, HashMap<String, Module>>,
+) {
+	let module_id = successor.get_module_id().clone();
+	let previous_version = registered_modules
+		.get(&module_id)
+		.or_else(|| unregistered_modules.get(&module_id))
+		.unwrap()
+		.get_version()
+		.clone();
+	logger::info_with!(
+		Fields::new().module_id(&module_id),
+		"Upgrading module '{}' from version '{}' to '{}'...",
+		module_id,
+		previous_version,
+		successor.get_version()
+	);
+
+	// Every module that is activated right now should stay activated with the successor in place.
+	// That includes the module itself, so a successor of an activated module with dependencies
+	// that aren't met is rejected too
+	let (dependents, unmet_dependencies): (Map<String, Value>, Vec<UnmetDependency>) = {
+		let mut modules: HashMap<&String, &Module> = registered_modules
+			.iter()
+			.chain(unregistered_modules.iter())
+			.collect();
+		modules.insert(&module_id, &successor);
+		let mut all_unmet_dependencies = dependency_graph::get_unmet_dependencies(&modules);
+		let unmet_dependencies = all_unmet_dependencies
+			.remove(&module_id)
+			.unwrap_or_default();
+		let mut dependents: Map<String, Value> = all_unmet_dependencies
+			.into_iter()
+			.filter(|(dependent, unmet)| {
+				!unmet.is_empty() && registered_modules.contains_key(dependent)
+			})
+			.map(|(dependent, unmet)| {
+				(
+					dependent,
+					Value::Array(unmet.iter().map(UnmetDependency::to_json).collect()),
+				)
+			})
+			.collect();
+		if !unmet_dependencies.is_empty() && registered_modules.contains_key(&module_id) {
+			dependents.insert(
+				module_id.clone(),
+				Value::Array(
+					unmet_dependencies
+						.iter()
+						.map(UnmetDependency::to_json)
+						.collect(),
+				),
+			);
+		}
+		(dependents, unmet_dependencies)
+	};
+	if !dependents.is_empty() {
+		logger::debug!(
+			"The upgrade would leave {} module(s) with unmet dependencies. Sending error...",
+			dependents.len()
+		);
+		MODULE_UUID_TO_ID
+			.write()
+			.await
+			.remove(module_comm.get_uuid());
+		drop(registered_modules);
+		drop(unregistered_modules);
+		send_module_comm(
+			module_comm,
+			&json!({
+				request_keys::REQUEST_ID: request_id,
+				request_keys::TYPE: request_types::ERROR,
+				request_keys::ERROR: errors::INCOMPATIBLE_UPGRADE,
+				request_keys::DEPENDENTS: dependents
+			}),
+		)
+		.await;
+		logger::verbose!(
+			"{} error sent",
+			get_error_name(errors::INCOMPATIBLE_UPGRADE)
+		);
+		metrics::record_error(
+			Some(module_comm.get_uuid()),
+			errors::INCOMPATIBLE_UPGRADE,
+			get_error_name(errors::INCOMPATIBLE_UPGRADE),
+		);
+		return;
+	}
+
+	let module = registered_modules
+		.get_mut(&module_id)
+		.or_else(|| unregistered_modules.get_mut(&module_id))
+		.unwrap();
+	logger::verbose("Moving the module over to the successor...");
+	for module_uuid in module.get_active_connection_uuids() {
+		module.drain_connection(module_uuid);
+	}
+	module.add_connection(*module_comm.get_uuid(), module_comm.clone_sender());
+	module.set_module_uuid(*module_comm.get_uuid());
+	module.set_version(successor.get_version().clone());
+	module.set_dependencies(successor.get_dependencies().clone());
+	module.set_optional_dependencies(successor.get_optional_dependencies().clone());
+	module.set_replicable(successor.is_replicable());
+	module.set_backpressure(successor.get_backpressure());
+	// The successor declares its own functions and registers its own hooks
+	module.clear_declarations();
+	// A module that isn't activated yet is activated once the dependencies of the successor are met
+	let activated = module.is_registered() && unmet_dependencies.is_empty();
+	logger::verbose("Module moved over to the successor");
+	if !unmet_dependencies.is_empty() {
+		logger::info_with!(
+			Fields::new().module_id(&module_id),
+			"Module '{}' can't be activated yet. It has {} unmet dependencies",
+			module_id,
+			unmet_dependencies.len()
+		);
+		for unmet_dependency in unmet_dependencies.iter() {
+			logger::debug(&unmet_dependency.get_description());
+		}
+	}
+
+	// New function calls go to the successor as soon as the modules are unlocked, so the
+	// response (and the activation hook) are queued before that, without waiting on the queue
+	logger::verbose("Notifying successful module registration...");
+	force_send_module_comm(
+		module_comm,
+		&get_registration_response(
+			request_id,
+			activated,
+			&unmet_dependencies,
+			&issue_resumption_token(module_comm.get_uuid()).await,
+		),
+	);
+	logger::verbose("Notification successful");
+
+	if activated {
+		logger::verbose("Triggering activation hook on the successor...");
+		force_send_module_comm(
+			module_comm,
+			&json!({
+				request_keys::REQUEST_ID: generate_request_id().await,
+				request_keys::TYPE: request_types::TRIGGER_HOOK_RESPONSE,
+				request_keys::HOOK: format!("{}.{}", constants::APP_NAME, juno_hooks::ACTIVATED),
+				request_keys::DATA: Map::new()
+			}),
+		);
+		logger::verbose("Activation hook triggered");
+	}
+
+	drop(registered_modules);
+	drop(unregistered_modules);
+
+	close_drained_connections().await;
+
+	logger::verbose("Notifying all modules of upgraded module...");
+	let juno_module = REGISTERED_MODULES
+		.read()
+		.await
+		.get(constants::APP_NAME)
+		.unwrap()
+		.clone();
+	trigger_hook(
+		&juno_module,
+		juno_hooks::MODULE_UPGRADED,
+		json!({
+			request_keys::MODULE_ID: module_id,
+			request_keys::VERSION: successor.get_version().to_string(),
+			request_keys::PREVIOUS_VERSION: previous_version.to_string()
+		})
+		.as_object()
+		.unwrap(),
+		false,
+		false,
+	)
+	.await;
+	logger::verbose("All modules notified of upgraded module");
+
+	// Modules that were waiting for a newer version of this module can now be activated
+	recalculate_all_module_dependencies().await;
+}
+
+async fn is_authenticated(module_comm: &ModuleComm, module_id: &str, request: &Value) -> bool {
+	if module_comm.is_internal() {
+		return true;
+	}
+	match CREDENTIALS.read().await.as_ref() {
+		None => true,
+		Some(credentials) => {
+			let token = request[request_keys::TOKEN].as_str().unwrap_or_default();
+			credentials::is_authorized(credentials, module_id, token)
+		}
+	}
+}
+
+// Closes the connection if it has failed to authenticate too many times
+async fn on_authentication_failure(module_comm: &ModuleComm) {
+	let max_failures = *MAX_AUTHENTICATION_FAILURES.read().await;
+	let mut authentication_failures = AUTHENTICATION_FAILURES.write().await;
+	let failures = authentication_failures
+		.entry(*module_comm.get_uuid())
+		.or_insert(0);
+	*failures += 1;
+
+	if max_failures != 0 && *failures >= max_failures {
+		logger::warn_with!(
+			Fields::new().connection_id(module_comm.get_uuid()),
+			"Connection {} failed to authenticate {} times. Closing connection...",
+			module_comm.get_uuid(),
+			failures
+		);
+		drop(authentication_failures);
+		module_comm.close_sender().await;
+	}
+}
+
+// Takes what the request uses from the rate limits of the module that sent it. If that's
+// over any of the limits, returns which one, along with how long to wait before retrying
+async fn check_rate_limits(
+	module_comm: &ModuleComm,
+	r#type: u64,
+	bytes: usize,
+) -> Option<(&'static str, Duration)> {
+	if module_comm.is_internal() {
+		return None;
+	}
+	// Modules are only limited once they've registered
+	let module_id = get_module_id_for_uuid(module_comm.get_uuid()).await?;
+
+	let mut usage = vec![];
+	match r#type {
+		request_types::FUNCTION_CALL_REQUEST => usage.push((rate_limits::CALLS, 1)),
+		request_types::TRIGGER_HOOK_REQUEST => usage.push((rate_limits::TRIGGERS, 1)),
+		_ => {}
+	}
+	// Holding back responses and pongs would only make the module look broken to everyone else
+	if r#type != request_types::FUNCTION_CALL_RESPONSE && r#type != request_types::PONG {
+		usage.push((rate_limits::BYTES, bytes as u64));
+	}
+
+	let policy = POLICY.read().await;
+	let default_rate_limits = DEFAULT_RATE_LIMITS.read().await;
+	let mut rate_limiters = RATE_LIMITERS.write().await;
+	let buckets = rate_limiters
+		.entry(module_id.clone())
+		.or_insert_with(HashMap::new);
+	let usage: Vec<(&'static str, u64, u64)> = usage
+		.into_iter()
+		.map(|(kind, amount)| {
+			let limit = get_rate_limit(&policy, &default_rate_limits, &module_id, kind);
+			(kind, amount, limit)
+		})
+		.collect();
+
+	// Nothing is taken from any of the buckets unless the request fits in all of them
+	for (kind, amount, limit) in usage.iter() {
+		let bucket = buckets
+			.entry(kind)
+			.or_insert_with(|| TokenBucket::new(*limit));
+		if let Err(retry_after) = bucket.check(*amount, *limit) {
+			return Some((kind, retry_after));
+		}
+	}
+	for (kind, amount, limit) in usage {
+		buckets.get_mut(kind).unwrap().take(amount, limit);
+	}
+	None
+}
+
+fn get_rate_limit(
+	policy: &Option<Policy>,
+	default_rate_limits: &RateLimits,
+	module_id: &str,
+	kind: &str,
+) -> u64 {
+	policy
+		.as_ref()
+		.and_then(|policy| policy.get_rate_limits(module_id))
+		.and_then(|rate_limits| rate_limits.get(kind))
+		.or_else(|| default_rate_limits.get(kind))
+		.unwrap_or(0)
+}
+
+// The trace context a request is part of. Requests without one (or with an invalid one) start a new trace
+fn get_trace_context(request: &Value) -> TraceContext {
+	if request[request_keys::TRACE].is_null() {
+		return TraceContext::new_root();
+	}
+	match request[request_keys::TRACE]
+		.as_str()
+		.and_then(TraceContext::parse)
+	{
+		Some(trace) => trace,
+		None => {
+			logger::debug("trace is not a valid traceparent. Starting a new trace...");
+			TraceContext::new_root()
+		}
+	}
+}
+
+// Parses a map of moduleIds to SemVer version requirements. Returns None if the map is malformed
+fn get_dependency_map(
+	dependencies: Option<&Map<String, Value>>,
+) -> Option<HashMap<String, VersionReq>> {
+	let mut dependency_map = HashMap::new();
+
+	// If dependencies are not null, then populate the dependency_map
+	if let Some(dependencies) = dependencies {
+		logger::verbose("Dependency is not null. Populating hashmap...");
+		for dependency in dependencies.keys() {
+			if !dependencies[dependency].is_string() {
+				logger::debug!(
+					"Dependency value for key {} is not a string. Sending error...",
+					dependency
+				);
+				return None;
+			}
+			let dependency_requirement =
+				VersionReq::parse(dependencies[dependency].as_str().unwrap());
+			if dependency_requirement.is_err() {
+				logger::debug!("Dependency value for key {} is not a valid SemVer version requirement. Sending error...", dependency);
+				return None;
+			}
+			dependency_map.insert(dependency.clone(), dependency_requirement.unwrap());
+		}
+		logger::verbose!(
+			"HashMap populated with {} dependencies",
+			dependency_map.len()
+		);
+	} else {
+		logger::verbose("Dependency is null. Pre-assigning a new HashMap");
+	}
+
+	Some(dependency_map)
+}
+
+fn get_registration_response(
+	request_id: &str,
+	activated: bool,
+	unmet_dependencies: &[UnmetDependency],
+	resumption_token: &str,
+) -> Value {
+	let mut response = get_dependency_status_object(activated, unmet_dependencies);
+	response[request_keys::REQUEST_ID] = Value::String(String::from(request_id));
+	response[request_keys::TYPE] = json!(request_types::REGISTER_MODULE_RESPONSE);
+	response[request_keys::RESUMPTION_TOKEN] = Value::String(String::from(resumption_token));
+	response
+}
+
+fn get_dependency_status_object(activated: bool, unmet_dependencies: &[UnmetDependency]) -> Value {
+	json!({
+		request_keys::ACTIVATED: activated,
+		request_keys::UNMET_DEPENDENCIES: unmet_dependencies
+			.iter()
+			.map(UnmetDependency::to_json)
+			.collect::<Vec<Value>>()
+	})
+}
+
 async fn handle_declare_function(module_comm: &ModuleComm, request_id: &str, request: &Value) {
 	let module_id = get_module_id_for_uuid(&module_comm.get_uuid()).await;
 
@@ -393,6 +1543,7 @@ async fn handle_declare_function(module_comm: &ModuleComm, request_id: &str, req
 	// Check if module is registered
 	if !registered_modules.contains_key(&module_id) {
 		logger::debug("This module is not registered. Sending error...");
+		drop(registered_modules);
 		send_error(module_comm, request_id, errors::UNREGISTERED_MODULE).await;
 		return;
 	}
@@ -401,24 +1552,65 @@ async fn handle_declare_function(module_comm: &ModuleComm, request_id: &str, req
 	let function = request[request_keys::FUNCTION].as_str();
 	if function.is_none() {
 		logger::debug("Function is not parsable as a string. Sending error...");
+		drop(registered_modules);
 		send_error(module_comm, request_id, errors::MALFORMED_REQUEST).await;
 		return;
 	}
 	let function = function.unwrap();
 	let function = String::from(function);
+
+	let mut schemas = vec![];
+	for schema_key in [
+		request_keys::ARGUMENTS_SCHEMA,
+		request_keys::RESPONSE_SCHEMA,
+	]
+	.iter()
+	{
+		let schema = &request[*schema_key];
+		if schema.is_null() {
+			schemas.push(None);
+		} else if json_schema::is_valid_schema(schema) {
+			schemas.push(Some(schema.clone()));
+		} else {
+			logger::debug!(
+				"{} is not a valid JSON schema. Sending error...",
+				schema_key
+			);
+			drop(registered_modules);
+			send_error(module_comm, request_id, errors::MALFORMED_REQUEST).await;
+			return;
+		}
+	}
+	let response_schema = schemas.pop().unwrap();
+	let argument_schema = schemas.pop().unwrap();
+
 	if !module.is_function_declared(&function) {
 		module.declare_function(function.clone());
-		logger::info(&format!(
+		logger::info_with!(
+			Fields::new()
+				.module_id(&module_id)
+				.function(&function)
+				.request_id(request_id),
 			"Function '{}' declared on module '{}'",
-			function, module_id
-		));
+			function,
+			module_id
+		);
 	} else {
-		logger::warn("This function is already declared. No need to register it again");
+		logger::warn_with!(
+			Fields::new()
+				.module_id(&module_id)
+				.function(&function)
+				.request_id(request_id),
+			"This function is already declared. No need to register it again"
+		);
 	}
+	module.set_function_schemas(&function, argument_schema, response_schema);
+
+	drop(registered_modules);
 
 	logger::verbose("Informing module of successful function declaration...");
-	send_module(
-		module,
+	send_module_comm(
+		module_comm,
 		&json!(
 		{
 			request_keys::REQUEST_ID: request_id,
@@ -430,6 +1622,73 @@ async fn handle_declare_function(module_comm: &ModuleComm, request_id: &str, req
 	logger::verbose("Success response has been sent");
 }
 
+async fn handle_undeclare_function(module_comm: &ModuleComm, request_id: &str, request: &Value) {
+	let module_id = get_module_id_for_uuid(module_comm.get_uuid()).await;
+
+	if module_id.is_none() {
+		logger::debug("moduleId not found. Sending error...");
+		send_error(module_comm, request_id, errors::UNREGISTERED_MODULE).await;
+		return;
+	}
+	let module_id = module_id.unwrap();
+
+	let mut registered_modules = REGISTERED_MODULES.write().await;
+
+	// Check if module is registered
+	if !registered_modules.contains_key(&module_id) {
+		logger::debug("This module is not registered. Sending error...");
+		drop(registered_modules);
+		send_error(module_comm, request_id, errors::UNREGISTERED_MODULE).await;
+		return;
+	}
+
+	let module = registered_modules.get_mut(&module_id).unwrap();
+	let function = request[request_keys::FUNCTION].as_str();
+	if function.is_none() {
+		logger::debug("Function is not parsable as a string. Sending error...");
+		drop(registered_modules);
+		send_error(module_comm, request_id, errors::MALFORMED_REQUEST).await;
+		return;
+	}
+	let function = function.unwrap();
+	if module.is_function_declared(function) {
+		// Calls that are already in flight are tracked by their requestId, so they can still be responded to
+		module.undeclare_function(function);
+		logger::info_with!(
+			Fields::new()
+				.module_id(&module_id)
+				.function(&function)
+				.request_id(request_id),
+			"Function '{}' undeclared on module '{}'",
+			function,
+			module_id
+		);
+	} else {
+		logger::warn_with!(
+			Fields::new()
+				.module_id(&module_id)
+				.function(&function)
+				.request_id(request_id),
+			"This function is not declared. No need to undeclare it"
+		);
+	}
+
+	drop(registered_modules);
+
+	logger::verbose("Informing module of successful function undeclaration...");
+	send_module_comm(
+		module_comm,
+		&json!(
+		{
+			request_keys::REQUEST_ID: request_id,
+			request_keys::TYPE: request_types::UNDECLARE_FUNCTION_RESPONSE,
+			request_keys::FUNCTION: function
+		}),
+	)
+	.await;
+	logger::verbose("Success response has been sent");
+}
+
 async fn handle_function_call(module_comm: &ModuleComm, request_id: &str, request: &Value) {
 	let module_id = get_module_id_for_uuid(&module_comm.get_uuid()).await;
 
@@ -455,10 +1714,15 @@ async fn handle_function_call(module_comm: &ModuleComm, request_id: &str, reques
 		return;
 	}
 	let function = function.unwrap();
-	logger::info(&format!(
+	logger::info_with!(
+		Fields::new()
+			.module_id(&module_id)
+			.function(function)
+			.request_id(request_id),
 		"Got request from module '{}' to call function '{}'",
-		module_id, function
-	));
+		module_id,
+		function
+	);
 	let function_name = is_function_name(function);
 
 	if function_name.is_none() {
@@ -469,55 +1733,156 @@ async fn handle_function_call(module_comm: &ModuleComm, request_id: &str, reques
 		return;
 	}
 	let (module_name, function_name) = function_name.unwrap();
-	logger::info(&format!(
+
+	let can_call = POLICY
+		.read()
+		.await
+		.as_ref()
+		.map(|policy| policy.can_call(&module_id, function))
+		.unwrap_or(true);
+	if !can_call {
+		logger::warn_with!(
+			Fields::new()
+				.module_id(&module_id)
+				.function(function)
+				.request_id(request_id),
+			"Module '{}' is not allowed to call the function '{}'. Sending error...",
+			module_id,
+			function
+		);
+		send_error(module_comm, request_id, errors::FORBIDDEN).await;
+		return;
+	}
+
+	logger::info_with!(
+		Fields::new()
+			.module_id(&module_id)
+			.function(function)
+			.request_id(request_id),
 		"Calling function '{}' in module '{}'",
-		function_name, module_name
-	));
+		function_name,
+		module_name
+	);
 
-	let registered_modules = REGISTERED_MODULES.read().await;
-	if !registered_modules.contains_key(&module_name) {
-		logger::debug(&format!(
+	let timeout = if request[request_keys::TIMEOUT].is_null() {
+		*FUNCTION_CALL_TIMEOUT.read().await
+	} else if let Some(timeout) = request[request_keys::TIMEOUT].as_u64() {
+		timeout
+	} else {
+		logger::debug("Timeout is not parsable as a positive integer. Sending error...");
+		send_error(module_comm, request_id, errors::MALFORMED_REQUEST).await;
+		return;
+	};
+
+	// Nothing is sent while holding on to the modules, since sending can wait on a full queue
+	let receiver_module = REGISTERED_MODULES.read().await.get(&module_name).cloned();
+	if receiver_module.is_none() {
+		logger::debug!(
 			"The module '{}' is not registered. Sending error...",
 			module_name
-		));
+		);
 		send_error(module_comm, request_id, errors::UNKNOWN_MODULE).await;
 		return;
 	}
 
-	let receiver_module = registered_modules.get(&module_name).unwrap();
+	let receiver_module = receiver_module.unwrap();
 	if !receiver_module.is_function_declared(&function_name) {
-		logger::debug(&format!(
+		logger::debug!(
 			"The function '{}' is not declared. Sending error...",
 			function_name
-		));
+		);
 		send_error(module_comm, request_id, errors::UNKNOWN_FUNCTION).await;
 		return;
 	}
 
+	let mut response = request.clone();
+	logger::verbose("Cloning request to send as response");
+	if request[request_keys::ARGUMENTS].as_object() == None {
+		logger::debug("The call to function had no arguments (or the arguments were not an object). A new, empty object will be assigned");
+		response[request_keys::ARGUMENTS] = Value::Object(Map::new());
+	}
+
+	if let Some(argument_schema) = receiver_module.get_argument_schema(&function_name) {
+		logger::verbose("Validating the arguments against the function's schema...");
+		let validation_errors = json_schema::validate(
+			argument_schema,
+			&response[request_keys::ARGUMENTS],
+			request_keys::ARGUMENTS,
+		);
+		if !validation_errors.is_empty() {
+			logger::debug!(
+				"The call to function '{}' had {} invalid argument(s). Sending error...",
+				function,
+				validation_errors.len()
+			);
+			send_validation_error(module_comm, request_id, validation_errors).await;
+			return;
+		}
+	}
+
+	let trace = get_trace_context(request);
 	let mut request_origins = REQUEST_ORIGINS.write().await;
 	let request_id_heap = String::from(request_id);
-	if request_origins.contains_key(&request_id_heap) {
-		if request_origins[&request_id_heap] != module_id {
+	let call_id = NEXT_CALL_ID.fetch_add(1, Ordering::Relaxed);
+	let target_module_uuid = select_module_connection(&receiver_module, &request_origins);
+	if let Some(previous_origin) = request_origins.get(&request_id_heap) {
+		if previous_origin.get_module_uuid() != module_comm.get_uuid() {
+			drop(request_origins);
 			// There's already a requestId that's supposed to return to
 			// a different module. Let the module know that it's invalid
 			// so that we can prevent response-hijacking.
-			logger::error(&format!("The call to function '{}' had a requestId '{}', which is already declared. In order to prevent request hijacking, this request will be errored. Sending error...", function, request_id));
+			logger::error_with!(
+				Fields::new()
+					.module_id(&module_id)
+					.function(function)
+					.request_id(request_id),
+				"The call to function '{}' had a requestId '{}', which is already declared. In order to prevent request hijacking, this request will be errored. Sending error...",
+				function,
+				request_id
+			);
 			send_error(module_comm, request_id, errors::INVALID_REQUEST_ID).await;
 			return;
 		} else {
-			logger::debug(&format!("There already seems to be a request to module '{}' with the requestId '{}'. This may or may not be intended. Are you sending the same request twice?", module_name, request_id));
+			logger::debug_with!(
+				Fields::new()
+					.module_id(&module_id)
+					.function(function)
+					.request_id(request_id),
+				"There already seems to be a request to module '{}' with the requestId '{}'. This may or may not be intended. Are you sending the same request twice?",
+				module_name,
+				request_id
+			);
 		}
-	} else {
-		logger::verbose("Registering the requestId along with it's origin module.");
-		request_origins.insert(request_id_heap, module_id.clone());
 	}
+	let span = Span::start(
+		&trace,
+		format!("call to '{}' from module '{}'", function, module_id),
+	);
+	logger::verbose("Registering the requestId along with it's origin module.");
+	// A call with the same requestId restarts the timeout for the latest call
+	let previous_origin = request_origins.insert(
+		request_id_heap.clone(),
+		RequestOrigin::new(
+			module_id.clone(),
+			*module_comm.get_uuid(),
+			target_module_uuid,
+			String::from(function),
+			call_id,
+			span.clone(),
+		),
+	);
 	drop(request_origins);
+	if let Some(previous_origin) = previous_origin {
+		previous_origin
+			.get_span()
+			.finish("replaced by a call with the same requestId");
+	}
 
-	let mut response = request.clone();
-	logger::verbose("Cloning request to send as response");
-	if request[request_keys::ARGUMENTS].as_object() == None {
-		logger::debug("The call to function had no arguments (or the arguments were not an object). A new, empty object will be assigned");
-		response[request_keys::ARGUMENTS] = Value::Object(Map::new());
+	if timeout == 0 {
+		logger::verbose("Function call has no timeout. Waiting for a response indefinitely");
+	} else {
+		logger::verbose!("Function call will time out in {} milliseconds", timeout);
+		task::spawn(expire_function_call(request_id_heap, call_id, timeout));
 	}
 
 	logger::verbose("Changing the value of function from 'module-name.function_name' to just 'function_name' in the request");
@@ -525,9 +1890,10 @@ async fn handle_function_call(module_comm: &ModuleComm, request_id: &str, reques
 
 	logger::verbose("Setting the caller of the function call...");
 	response[request_keys::CALLER] = Value::String(module_id);
+	response[request_keys::TRACE] = Value::String(span.get_context().to_traceparent());
 
 	logger::verbose("Proxying the request to the relevant module...");
-	send_module(receiver_module, &response).await;
+	send_module_to(&receiver_module, &target_module_uuid, &response).await;
 	logger::verbose("Function call proxied.");
 }
 
@@ -543,12 +1909,11 @@ async fn handle_function_response(module_comm: &ModuleComm, request_id: &str, re
 	let module_id = module_id.unwrap();
 
 	// Check if module is registered
-	let registered_modules = REGISTERED_MODULES.read().await;
-	if !registered_modules.contains_key(&module_id) {
-		logger::debug(&format!(
+	if !REGISTERED_MODULES.read().await.contains_key(&module_id) {
+		logger::debug!(
 			"The module '{}' is not registered. Sending error...",
 			module_id
-		));
+		);
 		send_error(module_comm, request_id, errors::UNREGISTERED_MODULE).await;
 		return;
 	}
@@ -556,34 +1921,281 @@ async fn handle_function_response(module_comm: &ModuleComm, request_id: &str, re
 	let mut request_origins = REQUEST_ORIGINS.write().await;
 
 	if !request_origins.contains_key(request_id) {
+		if TIMED_OUT_REQUESTS
+			.write()
+			.await
+			.remove(request_id)
+			.is_some()
+		{
+			// The caller has already been sent a TIMEOUT error.
+			// Drop the packet entirely
+			logger::warn_with!(
+				Fields::new()
+					.module_id(&module_id)
+					.request_id(request_id),
+				"The function response from module '{}' with requestId '{}' arrived after the call timed out. This response will be ignored.",
+				module_id,
+				request_id
+			);
+			return;
+		}
+
+		drop(request_origins);
 		// If the given requestId does not contain an origin,
 		// drop the packet entirely
-		logger::error(&format!("The function response with requestId '{}' does not contain an origin. The response might be malformed. Please ensure the function response has the same requestId as the function call", request_id));
+		logger::error_with!(
+			Fields::new()
+				.module_id(&module_id)
+				.request_id(request_id),
+			"The function response with requestId '{}' does not contain an origin. The response might be malformed. Please ensure the function response has the same requestId as the function call",
+			request_id
+		);
+		send_error(module_comm, request_id, errors::MALFORMED_REQUEST).await;
+		return;
+	}
+
+	// This requestId has completed its round-trip. Remove it from request_origins so that we can add the same one later on
+	let origin = request_origins.remove(request_id).unwrap();
+	let origin_module_id = origin.get_module_id();
+	drop(request_origins);
+	origin.get_span().finish("responded");
+	metrics::record_function_call(origin.get_function(), origin.get_span().get_elapsed());
+
+	let origin_module = REGISTERED_MODULES
+		.read()
+		.await
+		.get(origin_module_id)
+		.filter(|origin_module| origin_module.has_connection(origin.get_module_uuid()))
+		.cloned();
+	match origin_module {
+		Some(origin_module) => {
+			logger::info_with!(
+				Fields::new().module_id(&module_id).request_id(request_id),
+				"Sending response from module '{}' to caller module '{}'...",
+				module_id,
+				origin_module_id
+			);
+			// The caller gets back the trace it made the call with
+			let mut response = request.clone();
+			response[request_keys::TRACE] =
+				Value::String(origin.get_span().get_parent().to_traceparent());
+			send_module_to(&origin_module, origin.get_module_uuid(), &response).await;
+			logger::verbose("Function response to origin module successfully sent.");
+		}
+		_ => {
+			// The origin module has probably disconnected.
+			// Drop the packet entirely
+			logger::debug!("The function response meant for module '{}' is not registered (is the module still connected?). This packet will be ignored.", origin_module_id);
+		}
+	}
+
+	close_drained_connections().await;
+}
+
+async fn expire_function_call(request_id: String, call_id: u64, timeout: u64) {
+	task::sleep(Duration::from_millis(timeout)).await;
+
+	let mut request_origins = REQUEST_ORIGINS.write().await;
+	match request_origins.get(&request_id) {
+		Some(origin) if *origin.get_call_id() == call_id => {}
+		_ => {
+			// The call has already been responded to
+			return;
+		}
+	}
+	let origin = request_origins.remove(&request_id).unwrap();
+	let origin_module_id = origin.get_module_id();
+	drop(request_origins);
+	origin.get_span().finish("timed out");
+
+	TIMED_OUT_REQUESTS
+		.write()
+		.await
+		.insert(request_id.clone(), call_id);
+	close_drained_connections().await;
+
+	logger::warn_with!(
+		Fields::new()
+			.module_id(origin_module_id)
+			.request_id(&request_id),
+		"The function call with requestId '{}' from module '{}' timed out after {} milliseconds",
+		request_id,
+		origin_module_id,
+		timeout
+	);
+	let origin_module = REGISTERED_MODULES
+		.read()
+		.await
+		.get(origin_module_id)
+		.cloned();
+	if let Some(origin_module) = origin_module {
+		send_module_error(
+			&origin_module,
+			origin.get_module_uuid(),
+			&request_id,
+			errors::TIMEOUT,
+		)
+		.await;
+	} else {
+		logger::debug!("The module '{}' is not registered (is the module still connected?). The timeout will not be sent.", origin_module_id);
+	}
+
+	// Keep waiting for a late response for a while, after which the requestId is forgotten
+	task::sleep(Duration::from_millis(
+		constants::TIMED_OUT_REQUEST_RETENTION,
+	))
+	.await;
+
+	let mut timed_out_requests = TIMED_OUT_REQUESTS.write().await;
+	if timed_out_requests.get(&request_id) == Some(&call_id) {
+		timed_out_requests.remove(&request_id);
+	}
+}
+
+async fn handle_register_hook(module_comm: &ModuleComm, request_id: &str, request: &Value) {
+	// The module who is calling this function wants to listen for a hook
+	let module_id = get_module_id_for_uuid(&module_comm.get_uuid()).await;
+
+	if module_id.is_none() {
+		logger::debug("moduleId not found. Sending error...");
+		send_error(module_comm, request_id, errors::UNREGISTERED_MODULE).await;
+		return;
+	}
+	let module_id = module_id.unwrap();
+
+	let mut registered_modules = REGISTERED_MODULES.write().await;
+
+	let module = registered_modules.get_mut(&module_id);
+	if module.is_none() {
+		logger::debug("This module is not registered. Sending error...");
+		drop(registered_modules);
+		send_error(module_comm, request_id, errors::UNREGISTERED_MODULE).await;
+		return;
+	}
+	let module = module.unwrap();
+
+	let hook = request[request_keys::HOOK].as_str();
+	if hook == None {
+		logger::debug("Hook is not parsable as a string. Sending error...");
+		drop(registered_modules);
+		send_error(module_comm, request_id, errors::MALFORMED_REQUEST).await;
+		return;
+	}
+	let hook = String::from(hook.unwrap());
+
+	if !hook_pattern::is_valid(&hook) {
+		logger::debug!(
+			"The hook '{}' is not a valid hook pattern. Sending error...",
+			hook
+		);
+		drop(registered_modules);
 		send_error(module_comm, request_id, errors::MALFORMED_REQUEST).await;
 		return;
 	}
 
-	// This requestId has completed its round-trip. Remove it from request_origins so that we can add the same one later on
-	let origin_module_id = request_origins.remove(request_id).unwrap();
-
-	if !registered_modules.contains_key(&origin_module_id) {
-		// The origin module has probably disconnected.
-		// Drop the packet entirely
-		logger::debug(&format!("The function response meant for module '{}' is not registered (is the module still connected?). This packet will be ignored.", origin_module_id));
+	let policy = POLICY.read().await;
+	let can_listen = policy
+		.as_ref()
+		.map(|policy| policy.can_listen(&module_id, &hook))
+		.unwrap_or(true);
+	if !can_listen {
+		logger::warn_with!(
+			Fields::new()
+				.module_id(&module_id)
+				.hook(&hook)
+				.request_id(request_id),
+			"Module '{}' is not allowed to listen for the hook '{}'. Sending error...",
+			module_id,
+			hook
+		);
+		drop(policy);
+		drop(registered_modules);
+		send_error(module_comm, request_id, errors::FORBIDDEN).await;
 		return;
 	}
 
-	logger::info(&format!(
-		"Sending response from module '{}' to caller module '{}'...",
-		module_id, origin_module_id
-	));
-	send_module(registered_modules.get(&origin_module_id).unwrap(), request).await;
-	logger::verbose("Function response to origin module successfully sent.");
+	logger::info_with!(
+		Fields::new()
+			.module_id(&module_id)
+			.hook(&hook)
+			.request_id(request_id),
+		"Registering module '{}' for the hook '{}'...",
+		module_id,
+		hook
+	);
+	let sticky_hooks: Vec<(String, StickyHook)> = STICKY_HOOKS
+		.read()
+		.await
+		.iter()
+		.filter(|(hook_name, _)| hook_pattern::matches(&hook, hook_name))
+		.filter(|(hook_name, _)| {
+			policy
+				.as_ref()
+				.map(|policy| policy.can_listen(&module_id, hook_name))
+				.unwrap_or(true)
+		})
+		.map(|(hook_name, sticky_hook)| (hook_name.clone(), sticky_hook.clone()))
+		.collect();
+	drop(policy);
+
+	if !module.is_hook_pattern_registered(&hook) {
+		logger::verbose!("The hook '{}' is not registered. Registering hook...", hook);
+		module.register_hook(hook.clone());
+	} else {
+		logger::debug!(
+			"The hook '{}' is already registered. No need to register again.",
+			hook
+		);
+	}
+
+	drop(registered_modules);
+
+	logger::verbose("Hook registered. Sending success response to module...");
+	send_module_comm(
+		module_comm,
+		&json!(
+		{
+			request_keys::REQUEST_ID: request_id,
+			request_keys::TYPE: request_types::REGISTER_HOOK_RESPONSE
+		}),
+	)
+	.await;
+	logger::info_with!(
+		Fields::new()
+			.module_id(&module_id)
+			.hook(&hook)
+			.request_id(request_id),
+		"Hook registration done, and success response has been sent"
+	);
+
+	for (hook, (data, trace)) in sticky_hooks {
+		logger::verbose!(
+			"The hook '{}' is sticky. Sending its last data to the module...",
+			hook
+		);
+		let span = Span::start(
+			&trace,
+			format!("sticky hook '{}' to module '{}'", hook, module_id),
+		);
+		let delivery = send_module_comm(
+			module_comm,
+			&json!({
+				request_keys::REQUEST_ID: generate_request_id().await,
+				request_keys::TYPE: request_types::TRIGGER_HOOK_RESPONSE,
+				request_keys::HOOK: hook,
+				request_keys::DATA: data,
+				request_keys::TRACE: span.get_context().to_traceparent()
+			}),
+		)
+		.await;
+		span.finish(delivery.get_name());
+		logger::verbose("Sticky hook sent to module");
+	}
 }
 
-async fn handle_register_hook(module_comm: &ModuleComm, request_id: &str, request: &Value) {
-	// The module who is calling this function wants to listen for a hook
-	let module_id = get_module_id_for_uuid(&module_comm.get_uuid()).await;
+async fn handle_unregister_hook(module_comm: &ModuleComm, request_id: &str, request: &Value) {
+	// The module who is calling this function no longer wants to listen for a hook
+	let module_id = get_module_id_for_uuid(module_comm.get_uuid()).await;
 
 	if module_id.is_none() {
 		logger::debug("moduleId not found. Sending error...");
@@ -597,48 +2209,60 @@ async fn handle_register_hook(module_comm: &ModuleComm, request_id: &str, reques
 	let module = registered_modules.get_mut(&module_id);
 	if module.is_none() {
 		logger::debug("This module is not registered. Sending error...");
+		drop(registered_modules);
 		send_error(module_comm, request_id, errors::UNREGISTERED_MODULE).await;
 		return;
 	}
 	let module = module.unwrap();
 
 	let hook = request[request_keys::HOOK].as_str();
-	if hook == None {
+	if hook.is_none() {
 		logger::debug("Hook is not parsable as a string. Sending error...");
+		drop(registered_modules);
 		send_error(module_comm, request_id, errors::MALFORMED_REQUEST).await;
 		return;
 	}
-	let hook = String::from(hook.unwrap());
+	let hook = hook.unwrap();
 
-	logger::info(&format!(
-		"Registering module '{}' for the hook '{}'...",
-		module_id, hook
-	));
+	logger::info_with!(
+		Fields::new()
+			.module_id(&module_id)
+			.hook(hook)
+			.request_id(request_id),
+		"Unregistering module '{}' from the hook '{}'...",
+		module_id,
+		hook
+	);
 
-	if !module.is_hook_registered(&hook) {
-		logger::verbose(&format!(
-			"The hook '{}' is not registered. Registering hook...",
-			hook
-		));
-		module.register_hook(hook);
+	if module.is_hook_pattern_registered(hook) {
+		logger::verbose!("The hook '{}' is registered. Unregistering hook...", hook);
+		module.unregister_hook(hook);
 	} else {
-		logger::debug(&format!(
-			"The hook '{}' is already registered. No need to register again.",
+		logger::debug!(
+			"The hook '{}' is not registered. No need to unregister it.",
 			hook
-		));
+		);
 	}
 
-	logger::verbose("Hook registered. Sending success response to module...");
-	send_module(
-		module,
+	drop(registered_modules);
+
+	logger::verbose("Hook unregistered. Sending success response to module...");
+	send_module_comm(
+		module_comm,
 		&json!(
 		{
 			request_keys::REQUEST_ID: request_id,
-			request_keys::TYPE: request_types::REGISTER_HOOK_RESPONSE
+			request_keys::TYPE: request_types::UNREGISTER_HOOK_RESPONSE
 		}),
 	)
 	.await;
-	logger::info("Hook registration done, and success response has been sent");
+	logger::info_with!(
+		Fields::new()
+			.module_id(&module_id)
+			.hook(hook)
+			.request_id(request_id),
+		"Hook unregistration done, and success response has been sent"
+	);
 }
 
 async fn handle_trigger_hook(module_comm: &ModuleComm, request_id: &str, request: &Value) {
@@ -654,14 +2278,14 @@ async fn handle_trigger_hook(module_comm: &ModuleComm, request_id: &str, request
 
 	let registered_modules = REGISTERED_MODULES.read().await;
 
-	let module = registered_modules.get(&module_id);
+	let module = registered_modules.get(&module_id).cloned();
+	drop(registered_modules);
 	if module.is_none() {
 		logger::debug("This module is not registered. Sending error...");
 		send_error(module_comm, request_id, errors::UNREGISTERED_MODULE).await;
 		return;
 	}
-	let module = module.unwrap().clone();
-	drop(registered_modules);
+	let module = module.unwrap();
 
 	let hook = request[request_keys::HOOK].as_str();
 	if hook == None {
@@ -671,6 +2295,16 @@ async fn handle_trigger_hook(module_comm: &ModuleComm, request_id: &str, request
 	}
 	let hook = hook.unwrap();
 
+	let sticky = if request[request_keys::STICKY].is_null() {
+		false
+	} else if let Some(sticky) = request[request_keys::STICKY].as_bool() {
+		sticky
+	} else {
+		logger::debug("sticky is not a boolean. Sending error...");
+		send_error(module_comm, request_id, errors::MALFORMED_REQUEST).await;
+		return;
+	};
+
 	let data = request[request_keys::DATA].as_object();
 	let data = if data == None {
 		logger::debug("The triggered hook had no arguments (or the arguments were not an object). A new, empty object will be assigned");
@@ -680,21 +2314,50 @@ async fn handle_trigger_hook(module_comm: &ModuleComm, request_id: &str, request
 		data.unwrap().clone()
 	};
 
-	logger::info(&format!(
+	let hook_name = format!("{}.{}", module_id, hook);
+	let can_trigger = POLICY
+		.read()
+		.await
+		.as_ref()
+		.map(|policy| policy.can_trigger(&module_id, &hook_name))
+		.unwrap_or(true);
+	if !can_trigger {
+		logger::warn_with!(
+			Fields::new()
+				.module_id(&module_id)
+				.hook(&hook_name)
+				.request_id(request_id),
+			"Module '{}' is not allowed to trigger the hook '{}'. Sending error...",
+			module_id,
+			hook_name
+		);
+		send_error(module_comm, request_id, errors::FORBIDDEN).await;
+		return;
+	}
+
+	let trace = get_trace_context(request);
+
+	logger::info_with!(
+		Fields::new()
+			.module_id(&module_id)
+			.hook(&hook_name)
+			.request_id(request_id),
 		"Triggering hook '{}' from module '{}' on all modules...",
-		hook, module_id,
-	));
-	trigger_hook(&module, &hook, &data, false, false).await;
+		hook,
+		module_id
+	);
+	trigger_traced_hook(&module, &hook, &data, sticky, false, &trace).await;
 
 	logger::verbose(
 		"Hook triggered on all modules. Informing origin module of successful hook trigger...",
 	);
-	send_module(
-		&module,
+	send_module_comm(
+		module_comm,
 		&json!(
 		{
 			request_keys::REQUEST_ID: request_id,
-			request_keys::TYPE: request_types::TRIGGER_HOOK_RESPONSE
+			request_keys::TYPE: request_types::TRIGGER_HOOK_RESPONSE,
+			request_keys::TRACE: trace.to_traceparent()
 		}),
 	)
 	.await;
@@ -707,6 +2370,18 @@ async fn trigger_hook(
 	data: &Map<String, Value>,
 	sticky: bool,
 	force: bool,
+) {
+	// Hooks triggered by juno itself start a trace of their own
+	trigger_traced_hook(module, hook, data, sticky, force, &TraceContext::new_root()).await;
+}
+
+async fn trigger_traced_hook(
+	module: &Module,
+	hook: &str,
+	data: &Map<String, Value>,
+	sticky: bool,
+	force: bool,
+	trace: &TraceContext,
 ) {
 	// module is trying to trigger a hook.
 	// if force is true, all modules get the hook, regardless of whether they want it or not
@@ -714,49 +2389,79 @@ async fn trigger_hook(
 	let hook_name = module_id.clone() + "." + hook;
 
 	logger::verbose("Iterating all registered modules to send hook to...");
-	for registered_module in REGISTERED_MODULES.read().await.values() {
+	let registered_modules = REGISTERED_MODULES.read().await;
+	let request_origins = REQUEST_ORIGINS.read().await;
+	let policy = POLICY.read().await;
+	// Every module the hook is going to, along with the connection and whether it's forced on it.
+	// They're only sent to once the locks are let go of, since sending can wait on a full queue
+	let mut deliveries = vec![];
+	for registered_module in registered_modules.values() {
 		if force {
-			logger::verbose(&format!(
+			logger::verbose!(
 				"Hook is being forced onto module '{}'...",
 				registered_module.get_module_id()
-			));
-			send_module(
-				registered_module,
-				&json!({
-					request_keys::REQUEST_ID: generate_request_id().await,
-					request_keys::TYPE: request_types::TRIGGER_HOOK_RESPONSE,
-					request_keys::HOOK: hook_name,
-					request_keys::DATA: data
-				}),
-			)
-			.await;
-		} else if registered_module.is_hook_registered(&hook_name) {
-			logger::verbose(&format!(
-				"Module '{}' is listening for this hook. Sending hook to module...",
+			);
+		} else if !registered_module.is_hook_registered(&hook_name) {
+			logger::verbose!(
+				"Module '{}' is not listening for this hook. Hook is not being sent to module",
 				registered_module.get_module_id()
-			));
-			send_module(
-				registered_module,
-				&json!({
-					request_keys::REQUEST_ID: generate_request_id().await,
-					request_keys::TYPE: request_types::TRIGGER_HOOK_RESPONSE,
-					request_keys::HOOK: hook_name,
-					request_keys::DATA: data
-				}),
-			)
-			.await;
+			);
+			continue;
+		} else if matches!(policy.as_ref(), Some(policy) if !policy.can_listen(registered_module.get_module_id(), &hook_name))
+		{
+			// The policy might have changed since the module started listening for the hook
+			logger::debug!("Module '{}' is no longer allowed to listen for this hook. Hook is not being sent to module",
+				registered_module.get_module_id());
+			continue;
 		} else {
-			logger::verbose(&format!(
-				"Module '{}' is not listening for this hook. Hook is not being sent to module",
+			logger::verbose!(
+				"Module '{}' is listening for this hook. Sending hook to module...",
 				registered_module.get_module_id()
-			));
+			);
 		}
+		// Hooks are sent only once per module, even if it has replicas
+		let module_uuid = select_module_connection(registered_module, &request_origins);
+		deliveries.push((registered_module.clone(), module_uuid));
+	}
+	drop(policy);
+	drop(request_origins);
+	drop(registered_modules);
+
+	metrics::record_hook(&hook_name, deliveries.len() as u64);
+	for (registered_module, module_uuid) in deliveries {
+		let span = Span::start(
+			trace,
+			format!(
+				"hook '{}' to module '{}'",
+				hook_name,
+				registered_module.get_module_id()
+			),
+		);
+		let hook_data = json!({
+			request_keys::REQUEST_ID: generate_request_id().await,
+			request_keys::TYPE: request_types::TRIGGER_HOOK_RESPONSE,
+			request_keys::HOOK: hook_name,
+			request_keys::DATA: data,
+			request_keys::TRACE: span.get_context().to_traceparent()
+		});
+		let delivery = if force {
+			send_module_to(&registered_module, &module_uuid, &hook_data).await
+		} else {
+			send_hook_to(&registered_module, &module_uuid, &hook_data).await
+		};
+		span.finish(delivery.get_name());
 	}
 	logger::verbose("All registered modules have been processed");
 
 	if sticky {
-		logger::info("This hook is being stickied. Saving it for future modules...");
-		logger::warn("TODO stickying hooks is not implemented yet");
+		logger::info_with!(
+			Fields::new().hook(&hook_name),
+			"This hook is being stickied. Saving it for future modules..."
+		);
+		STICKY_HOOKS
+			.write()
+			.await
+			.insert(hook_name, (data.clone(), trace.clone()));
 	} else {
 		logger::verbose("This hook is not being stickied.");
 	}
@@ -773,41 +2478,50 @@ async fn trigger_hook_on(
 	// if force is true, all modules get the hook, regardless of whether they want it or not
 	let module_id = String::from(from_module);
 	let hook_name = module_id.clone() + "." + hook;
-	logger::info(&format!(
+	logger::info_with!(
+		Fields::new()
+			.module_id(to_module.get_module_id())
+			.hook(&hook_name),
 		"Triggering '{}' hook on module '{}'...",
 		hook_name,
 		to_module.get_module_id()
-	));
+	);
+
+	if !force && !to_module.is_hook_registered(&hook_name) {
+		logger::verbose("The module is not registered for the hook. Hook is not being sent");
+		metrics::record_hook(&hook_name, 0);
+		return;
+	}
 
-	if force {
+	let span = Span::start(
+		&TraceContext::new_root(),
+		format!(
+			"hook '{}' to module '{}'",
+			hook_name,
+			to_module.get_module_id()
+		),
+	);
+	let hook_data = json!({
+		request_keys::REQUEST_ID: generate_request_id().await,
+		request_keys::TYPE: request_types::TRIGGER_HOOK_RESPONSE,
+		request_keys::HOOK: hook_name,
+		request_keys::DATA: data,
+		request_keys::TRACE: span.get_context().to_traceparent()
+	});
+	let delivery = if force {
+		// Forced hooks are sent while the modules are locked, so they can't wait on a full queue
 		logger::verbose("Hook is being forced onto the module...");
-		send_module(
-			to_module,
-			&json!({
-				request_keys::REQUEST_ID: generate_request_id().await,
-				request_keys::TYPE: request_types::TRIGGER_HOOK_RESPONSE,
-				request_keys::HOOK: hook_name,
-				request_keys::DATA: data
-			}),
-		)
-		.await;
-	} else if to_module.is_hook_registered(&hook_name) {
+		force_send_module(to_module, &hook_data)
+	} else {
 		logger::verbose("The module is registered for the hook. Sending hook...");
-		send_module(
-			to_module,
-			&json!({
-				request_keys::REQUEST_ID: generate_request_id().await,
-				request_keys::TYPE: request_types::TRIGGER_HOOK_RESPONSE,
-				request_keys::HOOK: hook_name,
-				request_keys::DATA: data
-			}),
-		)
-		.await;
-	}
+		send_hook(to_module, &hook_data).await
+	};
+	span.finish(delivery.get_name());
+	metrics::record_hook(&hook_name, 1);
 	logger::verbose("Hook sent to module.");
 }
 
-async fn recalculate_all_module_dependencies() {
+async fn recalculate_all_module_dependencies() -> HashMap<String, Vec<UnmetDependency>> {
 	logger::verbose("Recalculating all module dependencies...");
 	// List of all modules whose dependencies weren't satisfied earlier but are satisfied now
 	let mut satisfied_modules: Vec<String> = vec![];
@@ -815,65 +2529,34 @@ async fn recalculate_all_module_dependencies() {
 	let mut registered_modules = REGISTERED_MODULES.write().await;
 	let mut unregistered_modules = UNREGISTERED_MODULES.write().await;
 
+	let unmet_dependencies = get_all_unmet_dependencies(&registered_modules, &unregistered_modules);
+
 	logger::verbose("Checking if any unregistered modules are satisfied...");
 	// recheck the dependencies for each unregistered module
-	for (module_id, module) in unregistered_modules.iter() {
-		// For each module, check if the dependencies are satisfied
-		let mut dependency_satisfied = true;
-
-		logger::verbose(&format!("Checking dependencies for module '{}'", module_id));
-		for (dependency, version_req) in module.get_dependencies() {
-			if registered_modules.contains_key(dependency) {
-				if !version_req.matches(&registered_modules.get(dependency).unwrap().get_version())
-				{
-					logger::debug(&format!(
-						"Dependency '{}' is incompatible. Required '{}', present '{}'",
-						dependency,
-						version_req.to_string(),
-						registered_modules.get(dependency).unwrap().get_version()
-					));
-					dependency_satisfied = false;
-					break;
-				}
-			} else if unregistered_modules.contains_key(dependency) {
-				if !version_req
-					.matches(&unregistered_modules.get(dependency).unwrap().get_version())
-				{
-					logger::debug(&format!(
-						"Dependency '{}' is incompatible. Required '{}', present '{}'",
-						dependency,
-						version_req.to_string(),
-						registered_modules.get(dependency).unwrap().get_version()
-					));
-					dependency_satisfied = false;
-					break;
-				}
-			} else {
-				logger::debug(&format!(
-					"Dependency '{}' not present. Can't register this module",
-					dependency
-				));
-				dependency_satisfied = false;
-				break;
-			}
-		}
-
-		if dependency_satisfied {
-			logger::verbose(&format!(
+	for module_id in unregistered_modules.keys() {
+		logger::verbose!("Checking dependencies for module '{}'", module_id);
+		let unmet = &unmet_dependencies[module_id];
+		if unmet.is_empty() {
+			logger::verbose!(
 				"All dependencies for moduleId '{}' are satisfied.",
 				module_id
-			));
+			);
 			satisfied_modules.push(module_id.clone());
+		} else {
+			for unmet_dependency in unmet {
+				logger::debug!(
+					"Module '{}' can't be registered. {}",
+					module_id,
+					unmet_dependency.get_description()
+				);
+			}
 		}
 	}
 
 	logger::verbose("Registering all satisfied modules...");
 	// For all modules whose dependencies are now satisfied, register them
 	for module_id in satisfied_modules.iter() {
-		logger::verbose(&format!(
-			"Module {} is now satisfied. Registering...",
-			module_id
-		));
+		logger::verbose!("Module {} is now satisfied. Registering...", module_id);
 		let mut module = unregistered_modules.remove(module_id).unwrap();
 		module.set_registered(true);
 
@@ -893,6 +2576,20 @@ async fn recalculate_all_module_dependencies() {
 		logger::verbose("Module registered");
 	}
 	logger::verbose("All newly satisfied modules registered");
+
+	// Optional dependencies that were activated alongside are notified about below
+	for module_id in satisfied_modules.iter() {
+		notify_of_optional_dependencies(
+			&registered_modules[module_id],
+			&registered_modules,
+			&satisfied_modules,
+		)
+		.await;
+	}
+	let satisfied_versions: Vec<Version> = satisfied_modules
+		.iter()
+		.map(|module_id| registered_modules[module_id].get_version().clone())
+		.collect();
 	drop(registered_modules);
 	drop(unregistered_modules);
 
@@ -903,7 +2600,7 @@ async fn recalculate_all_module_dependencies() {
 		.unwrap()
 		.clone();
 	logger::verbose("Notifying all modules of activated modules...");
-	for module_id in satisfied_modules {
+	for (module_id, version) in satisfied_modules.into_iter().zip(satisfied_versions) {
 		trigger_hook(
 			&juno_module,
 			juno_hooks::MODULE_ACTIVATED,
@@ -914,6 +2611,7 @@ async fn recalculate_all_module_dependencies() {
 			false,
 		)
 		.await;
+		notify_optional_dependents(&module_id, &version, true).await;
 	}
 	logger::verbose("All modules notified of activated modules");
 
@@ -923,41 +2621,23 @@ async fn recalculate_all_module_dependencies() {
 	// List of all modules whose dependencies were satisfied but aren't now
 	let mut unsatisfied_modules: Vec<String> = vec![];
 
+	let unmet_dependencies = get_all_unmet_dependencies(&registered_modules, &unregistered_modules);
+
 	logger::verbose("Checking if any registered modules are no longer satisfied...");
 	// remove modules whose dependencies are no longer satisfied
-	for (module_id, module) in registered_modules.iter() {
-		// For each module, check if the dependencies are satisfied
-		let mut dependency_satisfied = true;
-
-		logger::verbose(&format!("Checking dependencies for module '{}'", module_id));
-		for (dependency, version_req) in module.get_dependencies() {
-			if registered_modules.contains_key(dependency) {
-				if !version_req.matches(&registered_modules.get(dependency).unwrap().get_version())
-				{
-					logger::debug(&format!(
-						"Dependency '{}' is incompatible. Required '{}', present '{}'",
-						dependency,
-						version_req.to_string(),
-						registered_modules.get(dependency).unwrap().get_version()
-					));
-					dependency_satisfied = false;
-					break;
-				}
-			} else {
-				logger::debug(&format!(
-					"Dependency '{}' not present or not registered. Can't keep this module registered",
-					dependency
-				));
-				dependency_satisfied = false;
-				break;
+	for module_id in registered_modules.keys() {
+		logger::verbose!("Checking dependencies for module '{}'", module_id);
+		let unmet = &unmet_dependencies[module_id];
+		if !unmet.is_empty() {
+			for unmet_dependency in unmet {
+				logger::debug!(
+					"Module '{}' can't be kept registered. {}",
+					module_id,
+					unmet_dependency.get_description()
+				);
 			}
-		}
-
-		if !dependency_satisfied {
-			logger::verbose(&format!(
-				"Not all dependencies for moduleId '{}' are satisfied. This module will be unregistered",
-				module_id
-			));
+			logger::verbose!("Not all dependencies for moduleId '{}' are satisfied. This module will be unregistered",
+				module_id);
 			unsatisfied_modules.push(module_id.clone());
 		}
 	}
@@ -965,10 +2645,10 @@ async fn recalculate_all_module_dependencies() {
 	logger::verbose("Unregistering all modules that are no longer satisfied...");
 	// For all modules whose dependencies are no longer satisfied, unregister them
 	for module_id in unsatisfied_modules.iter() {
-		logger::verbose(&format!(
+		logger::verbose!(
 			"Module {} is no longer satisfied. Unregistering...",
 			module_id
-		));
+		);
 		let mut module = registered_modules.remove(module_id).unwrap();
 		module.set_registered(false);
 
@@ -983,16 +2663,22 @@ async fn recalculate_all_module_dependencies() {
 		.await;
 		logger::verbose("DEACTIVATED trigger sent");
 
+		send_unmet_dependencies(&module, &unmet_dependencies[module_id]).await;
+
 		logger::verbose("Adding module to unregistered_modules...");
 		unregistered_modules.insert(module_id.clone(), module);
 		logger::verbose("Module unregistered");
 	}
 	logger::verbose("All module whose dependencies are no longer satisfied are unregistered");
+	let unsatisfied_versions: Vec<Version> = unsatisfied_modules
+		.iter()
+		.map(|module_id| unregistered_modules[module_id].get_version().clone())
+		.collect();
 	drop(registered_modules);
 	drop(unregistered_modules);
 
 	logger::verbose("Notifying all modules of deactivated modules...");
-	for module_id in unsatisfied_modules {
+	for (module_id, version) in unsatisfied_modules.into_iter().zip(unsatisfied_versions) {
 		trigger_hook(
 			&juno_module,
 			juno_hooks::MODULE_DEACTIVATED,
@@ -1003,10 +2689,155 @@ async fn recalculate_all_module_dependencies() {
 			false,
 		)
 		.await;
+		notify_optional_dependents(&module_id, &version, false).await;
 	}
 	logger::verbose("All modules notified of deactivated modules");
 
 	logger::verbose("All module dependencies recalculated");
+	unmet_dependencies
+}
+
+fn get_all_unmet_dependencies(
+	registered_modules: &HashMap<String, Module>,
+	unregistered_modules: &HashMap<String, Module>,
+) -> HashMap<String, Vec<UnmetDependency>> {
+	let modules = registered_modules
+		.iter()
+		.chain(unregistered_modules.iter())
+		.collect();
+	dependency_graph::get_unmet_dependencies(&modules)
+}
+
+async fn send_unmet_dependencies(module: &Module, unmet_dependencies: &[UnmetDependency]) {
+	logger::info_with!(
+		Fields::new().module_id(module.get_module_id()),
+		"Module '{}' can't be activated. It has {} unmet dependencies",
+		module.get_module_id(),
+		unmet_dependencies.len()
+	);
+	trigger_hook_on(
+		constants::APP_NAME,
+		module,
+		juno_hooks::DEPENDENCIES_UNMET,
+		json!({
+			request_keys::UNMET_DEPENDENCIES: unmet_dependencies
+				.iter()
+				.map(UnmetDependency::to_json)
+				.collect::<Vec<Value>>()
+		})
+		.as_object()
+		.unwrap(),
+		true,
+	)
+	.await;
+}
+
+// Lets every active module that optionally depends on module_id know that it appeared or disappeared.
+// Modules whose requirement doesn't match the version aren't told, since they can't use it anyway
+async fn notify_optional_dependents(module_id: &str, version: &Version, activated: bool) {
+	let hook = if activated {
+		juno_hooks::OPTIONAL_DEPENDENCY_ACTIVATED
+	} else {
+		juno_hooks::OPTIONAL_DEPENDENCY_DEACTIVATED
+	};
+	logger::verbose!(
+		"Notifying all modules that optionally depend on '{}'...",
+		module_id
+	);
+
+	for module in REGISTERED_MODULES.read().await.values() {
+		let version_req = module.get_optional_dependencies().get(module_id);
+		if version_req.is_none() || !version_req.unwrap().matches(version) {
+			continue;
+		}
+		trigger_hook_on(
+			constants::APP_NAME,
+			module,
+			hook,
+			json!({ request_keys::MODULE_ID: module_id })
+				.as_object()
+				.unwrap(),
+			true,
+		)
+		.await;
+	}
+	logger::verbose("All optional dependents notified");
+}
+
+// Lets a module that was just activated know which of its optional dependencies are already active.
+// Modules in excluded are skipped, since they notify their dependents themselves
+async fn notify_of_optional_dependencies(
+	module: &Module,
+	registered_modules: &HashMap<String, Module>,
+	excluded: &[String],
+) {
+	for (dependency, version_req) in module.get_optional_dependencies() {
+		if excluded.contains(dependency) {
+			continue;
+		}
+		let dependency_module = registered_modules.get(dependency);
+		if dependency_module.is_none() ||
+			!version_req.matches(dependency_module.unwrap().get_version())
+		{
+			continue;
+		}
+		trigger_hook_on(
+			constants::APP_NAME,
+			module,
+			juno_hooks::OPTIONAL_DEPENDENCY_ACTIVATED,
+			json!({ request_keys::MODULE_ID: dependency })
+				.as_object()
+				.unwrap(),
+			true,
+		)
+		.await;
+	}
+}
+
+// Closes the connections of upgraded modules that no longer have any function calls to respond to
+async fn close_drained_connections() {
+	let registered_modules = REGISTERED_MODULES.read().await;
+	let unregistered_modules = UNREGISTERED_MODULES.read().await;
+	let request_origins = REQUEST_ORIGINS.read().await;
+
+	for module in registered_modules
+		.values()
+		.chain(unregistered_modules.values())
+	{
+		for module_uuid in module.get_draining_connection_uuids() {
+			if request_origins.get_outstanding_calls(module_uuid) > 0 {
+				continue;
+			}
+			logger::info_with!(
+				Fields::new()
+					.module_id(module.get_module_id())
+					.connection_id(module_uuid),
+				"Connection {} of the previous version of module '{}' has no more pending function calls. Closing...",
+				module_uuid,
+				module.get_module_id()
+			);
+			module.close_connection(module_uuid).await;
+		}
+	}
+}
+
+// Picks the connection of a module with the least outstanding function calls.
+// Ties are broken in a round-robin fashion, so that replicas share the load evenly
+fn select_module_connection(module: &Module, request_origins: &PendingCalls) -> u128 {
+	let mut module_uuids = module.get_active_connection_uuids();
+	// Suspended connections only get data queued for later if there are no other connections
+	if module_uuids
+		.iter()
+		.any(|module_uuid| !module.is_connection_suspended(module_uuid))
+	{
+		module_uuids.retain(|module_uuid| !module.is_connection_suspended(module_uuid));
+	}
+	let offset = NEXT_CONNECTION_INDEX.fetch_add(1, Ordering::Relaxed);
+
+	(0..module_uuids.len())
+		.map(|index| module_uuids[(index + offset) % module_uuids.len()])
+		.min_by_key(|module_uuid| request_origins.get_outstanding_calls(module_uuid))
+		.unwrap_or(module.module_uuid)
 }
 
 fn is_function_name(name: &str) -> Option<(String, String)> {
@@ -1062,7 +2893,32 @@ async fn send_error(module_comm: &ModuleComm, request_id: &str, error_code: u32)
 		}),
 	)
 	.await;
-	let error_name = match error_code {
+	logger::verbose!("{} error sent", get_error_name(error_code));
+	metrics::record_error(
+		Some(module_comm.get_uuid()),
+		error_code,
+		get_error_name(error_code),
+	);
+}
+
+async fn send_module_error(module: &Module, module_uuid: &u128, request_id: &str, error_code: u32) {
+	send_module_to(
+		module,
+		module_uuid,
+		&json!({
+			request_keys::REQUEST_ID: request_id,
+			request_keys::TYPE: request_types::ERROR,
+			request_keys::ERROR: error_code
+		}),
+	)
+	.await;
+	logger::verbose!("{} error sent", get_error_name(error_code));
+	// This isn't an error for a request the module is making right now
+	metrics::record_error(None, error_code, get_error_name(error_code));
+}
+
+fn get_error_name(error_code: u32) -> &'static str {
+	match error_code {
 		errors::DUPLICATE_MODULE => "DUPLICATE_MODULE",
 		errors::INVALID_MODULE_ID => "INVALID_MODULE_ID",
 		errors::INVALID_REQUEST_ID => "INVALID_REQUEST_ID",
@@ -1071,15 +2927,97 @@ async fn send_error(module_comm: &ModuleComm, request_id: &str, error_code: u32)
 		errors::UNKNOWN_MODULE => "UNKNOWN_MODULE",
 		errors::UNKNOWN_REQUEST => "UNKNOWN_REQUEST",
 		errors::UNREGISTERED_MODULE => "UNREGISTERED_MODULE",
+		errors::TIMEOUT => "TIMEOUT",
+		errors::MODULE_DISCONNECTED => "MODULE_DISCONNECTED",
+		errors::INVALID_ARGUMENTS => "INVALID_ARGUMENTS",
+		errors::INCOMPATIBLE_UPGRADE => "INCOMPATIBLE_UPGRADE",
+		errors::INVALID_RESUMPTION_TOKEN => "INVALID_RESUMPTION_TOKEN",
+		errors::UNAUTHORIZED => "UNAUTHORIZED",
+		errors::FORBIDDEN => "FORBIDDEN",
+		errors::RATE_LIMITED => "RATE_LIMITED",
 		_ => "undefined",
-	};
-	logger::verbose(&format!("{} error sent", error_name));
+	}
+}
+
+async fn send_validation_error(
+	module_comm: &ModuleComm,
+	request_id: &str,
+	validation_errors: Vec<ValidationError>,
+) {
+	send_module_comm(
+		module_comm,
+		&json!({
+			request_keys::REQUEST_ID: request_id,
+			request_keys::TYPE: request_types::ERROR,
+			request_keys::ERROR: errors::INVALID_ARGUMENTS,
+			request_keys::FIELDS: validation_errors
+				.into_iter()
+				.map(|ValidationError { field, reason }| {
+					json!({
+						request_keys::FIELD: field,
+						request_keys::REASON: reason
+					})
+				})
+				.collect::<Vec<Value>>()
+		}),
+	)
+	.await;
+	logger::verbose!("{} error sent", get_error_name(errors::INVALID_ARGUMENTS));
+	metrics::record_error(
+		Some(module_comm.get_uuid()),
+		errors::INVALID_ARGUMENTS,
+		get_error_name(errors::INVALID_ARGUMENTS),
+	);
+}
+
+async fn send_rate_limited_error(
+	module_comm: &ModuleComm,
+	request_id: &str,
+	kind: &str,
+	retry_after: Duration,
+) {
+	send_module_comm(
+		module_comm,
+		&json!({
+			request_keys::REQUEST_ID: request_id,
+			request_keys::TYPE: request_types::ERROR,
+			request_keys::ERROR: errors::RATE_LIMITED,
+			request_keys::LIMIT: kind,
+			// Rounded up, so that retrying after it is enough
+			request_keys::RETRY_AFTER: retry_after.as_micros().div_ceil(1000) as u64
+		}),
+	)
+	.await;
+	logger::verbose!("{} error sent", get_error_name(errors::RATE_LIMITED));
+	metrics::record_error(
+		Some(module_comm.get_uuid()),
+		errors::RATE_LIMITED,
+		get_error_name(errors::RATE_LIMITED),
+	);
+}
+
+async fn send_module_comm(module_comm: &ModuleComm, data: &Value) -> Delivery {
+	module_comm.send(data.to_string() + "\n").await
+}
+
+fn force_send_module_comm(module_comm: &ModuleComm, data: &Value) -> Delivery {
+	module_comm.force_send(data.to_string() + "\n")
+}
+
+fn force_send_module(module: &Module, data: &Value) -> Delivery {
+	module.force_send(data.to_string() + "\n")
+}
+
+async fn send_module_to(module: &Module, module_uuid: &u128, data: &Value) -> Delivery {
+	module.send_to(module_uuid, data.to_string() + "\n").await
 }
 
-async fn send_module_comm(module_comm: &ModuleComm, data: &Value) {
-	module_comm.send(data.to_string() + "\n").await;
+async fn send_hook(module: &Module, data: &Value) -> Delivery {
+	module.send_hook(data.to_string() + "\n").await
 }
 
-async fn send_module(module: &Module, data: &Value) {
-	module.send(data.to_string() + "\n").await;
+async fn send_hook_to(module: &Module, module_uuid: &u128, data: &Value) -> Delivery {
+	module
+		.send_hook_to(module_uuid, data.to_string() + "\n")
+		.await
 }
@@ -0,0 +1,267 @@
+use crate::models::{Module, UnmetDependency};
+
+use std::collections::{HashMap, HashSet, VecDeque};
+
+// Works out which dependencies of each module are unmet. A module can only be activated if
+// every dependency is present with a matching version, the dependency can itself be activated,
+// and none of them depend back on the module. Optional dependencies never block activation,
+// unless they are present with a version that doesn't match the requirement.
+// Modules that can be activated get an empty list
+pub fn get_unmet_dependencies(
+	modules: &HashMap<&String, &Module>,
+) -> HashMap<String, Vec<UnmetDependency>> {
+	let mut unmet_dependencies = HashMap::new();
+
+	// Modules that are part of a cycle can never be activated. Find them first,
+	// so that the rest of the graph can be resolved without recursing forever
+	for (module_id, module) in modules.iter() {
+		let mut cycles = vec![];
+		for dependency in module.get_dependencies().keys() {
+			if let Some(path) = find_path(modules, dependency, module_id) {
+				let mut cycle = vec![(*module_id).clone()];
+				cycle.extend(path);
+				cycles.push(UnmetDependency::Cycle {
+					module_id: dependency.clone(),
+					path: cycle,
+				});
+			}
+		}
+		if !cycles.is_empty() {
+			unmet_dependencies.insert((*module_id).clone(), cycles);
+		}
+	}
+
+	for module_id in modules.keys() {
+		resolve(modules, module_id, &mut unmet_dependencies);
+	}
+
+	unmet_dependencies
+}
+
+fn resolve(
+	modules: &HashMap<&String, &Module>,
+	module_id: &str,
+	unmet_dependencies: &mut HashMap<String, Vec<UnmetDependency>>,
+) -> bool {
+	if let Some(unmet) = unmet_dependencies.get(module_id) {
+		return unmet.is_empty();
+	}
+
+	let module = modules[&String::from(module_id)];
+	let mut unmet = vec![];
+	for (dependency, version_req) in module.get_dependencies() {
+		match modules.get(dependency) {
+			None => unmet.push(UnmetDependency::Missing {
+				module_id: dependency.clone(),
+			}),
+			Some(dependency_module) if !version_req.matches(dependency_module.get_version()) => {
+				unmet.push(UnmetDependency::VersionMismatch {
+					module_id: dependency.clone(),
+					required: version_req.clone(),
+					present: dependency_module.get_version().clone(),
+				})
+			}
+			Some(_) => {
+				if !resolve(modules, dependency, unmet_dependencies) {
+					unmet.push(UnmetDependency::DependencyUnmet {
+						module_id: dependency.clone(),
+					});
+				}
+			}
+		}
+	}
+	for (dependency, version_req) in module.get_optional_dependencies() {
+		if let Some(dependency_module) = modules.get(dependency) {
+			if !version_req.matches(dependency_module.get_version()) {
+				unmet.push(UnmetDependency::VersionMismatch {
+					module_id: dependency.clone(),
+					required: version_req.clone(),
+					present: dependency_module.get_version().clone(),
+				});
+			}
+		}
+	}
+
+	let satisfied = unmet.is_empty();
+	unmet_dependencies.insert(String::from(module_id), unmet);
+	satisfied
+}
+
+// Finds the shortest path of dependencies from one module to another, if there is any
+fn find_path(
+	modules: &HashMap<&String, &Module>,
+	from_module: &str,
+	to_module: &str,
+) -> Option<Vec<String>> {
+	let mut visited = HashSet::new();
+	let mut queue = VecDeque::new();
+	queue.push_back(vec![String::from(from_module)]);
+
+	while let Some(path) = queue.pop_front() {
+		let module_id = path.last().unwrap();
+		if module_id == to_module {
+			return Some(path);
+		}
+		if !visited.insert(module_id.clone()) {
+			continue;
+		}
+		if let Some(module) = modules.get(module_id) {
+			for dependency in module.get_dependencies().keys() {
+				let mut next_path = path.clone();
+				next_path.push(dependency.clone());
+				queue.push_back(next_path);
+			}
+		}
+	}
+
+	None
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::{
+		models::{outbound_queue, Backpressure},
+		utils::constants::dependency_reasons,
+	};
+
+	use semver::{Version, VersionReq};
+
+	fn module(module_id: &str, version: &str, dependencies: &[(&str, &str)]) -> Module {
+		let (sender, _) = outbound_queue::channel(0, Backpressure::DropOldest);
+		let mut module = Module::new(
+			0,
+			String::from(module_id),
+			Version::parse(version).unwrap(),
+			sender,
+		);
+		module.set_dependencies(
+			dependencies
+				.iter()
+				.map(|(module_id, version_req)| {
+					(
+						String::from(*module_id),
+						VersionReq::parse(version_req).unwrap(),
+					)
+				})
+				.collect(),
+		);
+		module
+	}
+
+	fn unmet_dependencies_of(modules: &[Module]) -> HashMap<String, Vec<(String, String)>> {
+		let module_ids: Vec<String> = modules
+			.iter()
+			.map(|module| module.get_module_id().clone())
+			.collect();
+		let modules = module_ids.iter().zip(modules.iter()).collect();
+		get_unmet_dependencies(&modules)
+			.into_iter()
+			.map(|(module_id, unmet)| {
+				let unmet = unmet
+					.iter()
+					.map(|dependency| {
+						(
+							dependency.get_module_id().clone(),
+							String::from(dependency.get_reason()),
+						)
+					})
+					.collect();
+				(module_id, unmet)
+			})
+			.collect()
+	}
+
+	fn unmet(module_id: &str, reason: &str) -> (String, String) {
+		(String::from(module_id), String::from(reason))
+	}
+
+	#[test]
+	fn satisfied_modules_have_no_unmet_dependencies() {
+		let unmet_dependencies = unmet_dependencies_of(&[
+			module("a", "1.0.0", &[("b", "^1")]),
+			module("b", "1.2.0", &[]),
+		]);
+		assert!(unmet_dependencies["a"].is_empty());
+		assert!(unmet_dependencies["b"].is_empty());
+	}
+
+	#[test]
+	fn finds_missing_and_mismatched_dependencies() {
+		let unmet_dependencies = unmet_dependencies_of(&[
+			module("a", "1.0.0", &[("b", "^2"), ("c", "*")]),
+			module("b", "1.2.0", &[]),
+		]);
+		let mut unmet_a = unmet_dependencies["a"].clone();
+		unmet_a.sort();
+		assert_eq!(
+			unmet_a,
+			vec![
+				unmet("b", dependency_reasons::VERSION_MISMATCH),
+				unmet("c", dependency_reasons::MISSING)
+			]
+		);
+	}
+
+	#[test]
+	fn unmet_dependencies_are_transitive() {
+		let unmet_dependencies = unmet_dependencies_of(&[
+			module("a", "1.0.0", &[("b", "*")]),
+			module("b", "1.0.0", &[("c", "*")]),
+		]);
+		assert_eq!(
+			unmet_dependencies["a"],
+			vec![unmet("b", dependency_reasons::DEPENDENCY_UNMET)]
+		);
+		assert_eq!(
+			unmet_dependencies["b"],
+			vec![unmet("c", dependency_reasons::MISSING)]
+		);
+	}
+
+	#[test]
+	fn finds_cycles() {
+		let modules = [
+			module("a", "1.0.0", &[("b", "*")]),
+			module("b", "1.0.0", &[("a", "*")]),
+			module("c", "1.0.0", &[("a", "*")]),
+		];
+		let unmet_dependencies = unmet_dependencies_of(&modules);
+		assert_eq!(
+			unmet_dependencies["a"],
+			vec![unmet("b", dependency_reasons::CYCLE)]
+		);
+		assert_eq!(
+			unmet_dependencies["c"],
+			vec![unmet("a", dependency_reasons::DEPENDENCY_UNMET)]
+		);
+
+		let module_ids: Vec<String> = modules
+			.iter()
+			.map(|module| module.get_module_id().clone())
+			.collect();
+		let modules = module_ids.iter().zip(modules.iter()).collect();
+		match &get_unmet_dependencies(&modules)["a"][0] {
+			UnmetDependency::Cycle { path, .. } => assert_eq!(path, &["a", "b", "a"]),
+			_ => panic!("expected a cycle"),
+		}
+	}
+
+	#[test]
+	fn optional_dependencies_only_block_on_mismatch() {
+		let mut a = module("a", "1.0.0", &[]);
+		a.set_optional_dependencies(
+			vec![
+				(String::from("b"), VersionReq::parse("^2").unwrap()),
+				(String::from("c"), VersionReq::parse("*").unwrap()),
+			]
+			.into_iter()
+			.collect(),
+		);
+		let unmet_dependencies = unmet_dependencies_of(&[a, module("b", "1.0.0", &[])]);
+		assert_eq!(
+			unmet_dependencies["a"],
+			vec![unmet("b", dependency_reasons::VERSION_MISMATCH)]
+		);
+	}
+}
@@ -0,0 +1,304 @@
+use crate::utils::constants::request_types;
+
+use std::{
+	collections::{BTreeMap, HashSet},
+	fmt::Write,
+	sync::Mutex,
+	time::Duration,
+};
+
+// In seconds. The upper bounds of the buckets of the function call latency histograms
+const LATENCY_BUCKETS: [f64; 13] = [
+	0.001, 0.0025, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0,
+];
+
+lazy_static! {
+	static ref METRICS: Mutex<Metrics> = Mutex::new(Metrics::default());
+}
+
+#[derive(Default)]
+struct Metrics {
+	connections: u64,
+	// (request type, outcome) -> count
+	requests: BTreeMap<(&'static str, &'static str), u64>,
+	// (error code, error name) -> count
+	errors: BTreeMap<(u32, &'static str), u64>,
+	// module.function -> latency
+	function_calls: BTreeMap<String, Histogram>,
+	// module.hook -> (times triggered, modules it was delivered to)
+	hooks: BTreeMap<String, (u64, u64)>,
+	// Connections that were sent an error for the request they're currently making
+	failed_requests: HashSet<u128>,
+}
+
+struct Histogram {
+	buckets: [u64; LATENCY_BUCKETS.len()],
+	sum: f64,
+	count: u64,
+}
+
+// The state of juno at the time the metrics are rendered, as opposed to what is counted over time
+pub struct Gauges {
+	pub registered_modules: usize,
+	pub unregistered_modules: usize,
+	// (moduleId, connection, messages waiting to be written)
+	pub queue_depths: Vec<(String, u128, usize)>,
+	// (moduleId, hook deliveries dropped because the module wasn't reading fast enough)
+	pub dropped_messages: Vec<(String, u64)>,
+}
+
+pub fn on_connection_opened() {
+	METRICS.lock().unwrap().connections += 1;
+}
+
+pub fn on_connection_closed(module_uuid: &u128) {
+	let mut metrics = METRICS.lock().unwrap();
+	metrics.connections -= 1;
+	metrics.failed_requests.remove(module_uuid);
+}
+
+// Counts a request from a connection, once it's been handled. It failed if the connection was sent an error for it
+pub fn record_request(module_uuid: &u128, r#type: Option<u64>) {
+	let mut metrics = METRICS.lock().unwrap();
+	let outcome = if metrics.failed_requests.remove(module_uuid) {
+		"error"
+	} else {
+		"success"
+	};
+	*metrics
+		.requests
+		.entry((get_request_type_name(r#type), outcome))
+		.or_insert(0) += 1;
+}
+
+// Counts an error sent to a connection. If it's the connection that made the request
+// being handled, the request is counted as failed
+pub fn record_error(module_uuid: Option<&u128>, error_code: u32, error_name: &'static str) {
+	let mut metrics = METRICS.lock().unwrap();
+	*metrics.errors.entry((error_code, error_name)).or_insert(0) += 1;
+	if let Some(module_uuid) = module_uuid {
+		metrics.failed_requests.insert(*module_uuid);
+	}
+}
+
+pub fn record_function_call(function: &str, duration: Duration) {
+	let mut metrics = METRICS.lock().unwrap();
+	let histogram = metrics
+		.function_calls
+		.entry(String::from(function))
+		.or_insert(Histogram {
+			buckets: [0; LATENCY_BUCKETS.len()],
+			sum: 0.0,
+			count: 0,
+		});
+	let seconds = duration.as_secs_f64();
+	for (bucket, upper_bound) in histogram.buckets.iter_mut().zip(LATENCY_BUCKETS.iter()) {
+		if seconds <= *upper_bound {
+			*bucket += 1;
+		}
+	}
+	histogram.sum += seconds;
+	histogram.count += 1;
+}
+
+pub fn record_hook(hook: &str, deliveries: u64) {
+	let mut metrics = METRICS.lock().unwrap();
+	let (triggers, total_deliveries) = metrics.hooks.entry(String::from(hook)).or_insert((0, 0));
+	*triggers += 1;
+	*total_deliveries += deliveries;
+}
+
+// Renders every metric in the Prometheus text format
+pub fn render(gauges: &Gauges) -> String {
+	let metrics = METRICS.lock().unwrap();
+	let mut output = String::new();
+
+	write_header(
+		&mut output,
+		"juno_connections",
+		"gauge",
+		"Connections that are currently open",
+	);
+	writeln!(output, "juno_connections {}", metrics.connections).unwrap();
+
+	write_header(
+		&mut output,
+		"juno_modules",
+		"gauge",
+		"Modules that are currently connected, by whether they are registered",
+	);
+	writeln!(
+		output,
+		"juno_modules{{state=\"registered\"}} {}",
+		gauges.registered_modules
+	)
+	.unwrap();
+	writeln!(
+		output,
+		"juno_modules{{state=\"unregistered\"}} {}",
+		gauges.unregistered_modules
+	)
+	.unwrap();
+
+	write_header(
+		&mut output,
+		"juno_requests_total",
+		"counter",
+		"Requests handled, by type and outcome",
+	);
+	for ((r#type, outcome), count) in metrics.requests.iter() {
+		writeln!(
+			output,
+			"juno_requests_total{{type=\"{}\",outcome=\"{}\"}} {}",
+			r#type, outcome, count
+		)
+		.unwrap();
+	}
+
+	write_header(
+		&mut output,
+		"juno_errors_total",
+		"counter",
+		"Errors sent to modules, by error code",
+	);
+	for ((code, name), count) in metrics.errors.iter() {
+		writeln!(
+			output,
+			"juno_errors_total{{code=\"{}\",name=\"{}\"}} {}",
+			code, name, count
+		)
+		.unwrap();
+	}
+
+	write_header(
+		&mut output,
+		"juno_function_call_duration_seconds",
+		"histogram",
+		"Time taken for function calls to be responded to, by function",
+	);
+	for (function, histogram) in metrics.function_calls.iter() {
+		let function = escape_label(function);
+		for (bucket, upper_bound) in histogram.buckets.iter().zip(LATENCY_BUCKETS.iter()) {
+			writeln!(
+				output,
+				"juno_function_call_duration_seconds_bucket{{function=\"{}\",le=\"{}\"}} {}",
+				function, upper_bound, bucket
+			)
+			.unwrap();
+		}
+		writeln!(
+			output,
+			"juno_function_call_duration_seconds_bucket{{function=\"{}\",le=\"+Inf\"}} {}",
+			function, histogram.count
+		)
+		.unwrap();
+		writeln!(
+			output,
+			"juno_function_call_duration_seconds_sum{{function=\"{}\"}} {}",
+			function, histogram.sum
+		)
+		.unwrap();
+		writeln!(
+			output,
+			"juno_function_call_duration_seconds_count{{function=\"{}\"}} {}",
+			function, histogram.count
+		)
+		.unwrap();
+	}
+
+	write_header(
+		&mut output,
+		"juno_hook_triggers_total",
+		"counter",
+		"Times each hook was triggered",
+	);
+	for (hook, (triggers, _)) in metrics.hooks.iter() {
+		writeln!(
+			output,
+			"juno_hook_triggers_total{{hook=\"{}\"}} {}",
+			escape_label(hook),
+			triggers
+		)
+		.unwrap();
+	}
+
+	write_header(
+		&mut output,
+		"juno_hook_deliveries_total",
+		"counter",
+		"Modules each hook was delivered to, over all of its triggers",
+	);
+	for (hook, (_, deliveries)) in metrics.hooks.iter() {
+		writeln!(
+			output,
+			"juno_hook_deliveries_total{{hook=\"{}\"}} {}",
+			escape_label(hook),
+			deliveries
+		)
+		.unwrap();
+	}
+	drop(metrics);
+
+	write_header(
+		&mut output,
+		"juno_queue_depth",
+		"gauge",
+		"Messages waiting to be written to each connection of a module",
+	);
+	for (module_id, connection, depth) in gauges.queue_depths.iter() {
+		writeln!(
+			output,
+			"juno_queue_depth{{module=\"{}\",connection=\"{}\"}} {}",
+			escape_label(module_id),
+			connection,
+			depth
+		)
+		.unwrap();
+	}
+
+	write_header(
+		&mut output,
+		"juno_dropped_messages_total",
+		"counter",
+		"Hook deliveries dropped because a module wasn't reading fast enough",
+	);
+	for (module_id, dropped) in gauges.dropped_messages.iter() {
+		writeln!(
+			output,
+			"juno_dropped_messages_total{{module=\"{}\"}} {}",
+			escape_label(module_id),
+			dropped
+		)
+		.unwrap();
+	}
+
+	output
+}
+
+fn write_header(output: &mut String, name: &str, r#type: &str, help: &str) {
+	writeln!(output, "# HELP {} {}", name, help).unwrap();
+	writeln!(output, "# TYPE {} {}", name, r#type).unwrap();
+}
+
+fn escape_label(value: &str) -> String {
+	value
+		.replace('\\', "\\\\")
+		.replace('"', "\\\"")
+		.replace('\n', "\\n")
+}
+
+fn get_request_type_name(r#type: Option<u64>) -> &'static str {
+	match r#type {
+		Some(request_types::REGISTER_MODULE_REQUEST) => "registerModule",
+		Some(request_types::FUNCTION_CALL_REQUEST) => "functionCall",
+		Some(request_types::FUNCTION_CALL_RESPONSE) => "functionResponse",
+		Some(request_types::REGISTER_HOOK_REQUEST) => "registerHook",
+		Some(request_types::TRIGGER_HOOK_REQUEST) => "triggerHook",
+		Some(request_types::DECLARE_FUNCTION_REQUEST) => "declareFunction",
+		Some(request_types::UNREGISTER_HOOK_REQUEST) => "unregisterHook",
+		Some(request_types::UNDECLARE_FUNCTION_REQUEST) => "undeclareFunction",
+		Some(request_types::PING) => "ping",
+		Some(request_types::PONG) => "pong",
+		_ => "unknown",
+	}
+}
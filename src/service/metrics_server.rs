@@ -0,0 +1,105 @@
+use crate::{service::data_handler, utils::logger};
+
+use async_std::{
+	io::{self, BufReader, Error, ErrorKind},
+	net::{TcpListener, TcpStream},
+	prelude::*,
+	task,
+};
+use std::time::Duration;
+
+const METRICS_PATH: &str = "/metrics";
+const METRICS_CONTENT_TYPE: &str = "text/plain; version=0.0.4";
+// Nothing scraping the metrics needs more than this, so clients can't hold on to a connection,
+// or make juno buffer a never-ending request line
+const MAX_REQUEST_SIZE: u64 = 8192;
+const READ_TIMEOUT: Duration = Duration::from_secs(10);
+
+// Serves the metrics over HTTP, for Prometheus to scrape
+pub async fn listen(address: String) {
+	let listener = match TcpListener::bind(&address).await {
+		Ok(listener) => listener,
+		Err(err) => {
+			logger::error!("Unable to serve metrics on '{}': {}", address, err);
+			return;
+		}
+	};
+	logger::info!("Serving metrics on http://{}{}", address, METRICS_PATH);
+
+	let mut incoming = listener.incoming();
+	while let Some(stream) = incoming.next().await {
+		match stream {
+			Ok(stream) => {
+				task::spawn(handle_http_client(stream));
+			}
+			Err(err) => logger::error!("Error accepting metrics connection: {}", err),
+		}
+	}
+}
+
+async fn handle_http_client(stream: TcpStream) {
+	let request_line = match io::timeout(READ_TIMEOUT, read_request(&stream)).await {
+		Ok(request_line) => request_line,
+		Err(err) => {
+			logger::debug!("Error while reading metrics request: {}", err);
+			return;
+		}
+	};
+
+	let mut parts = request_line.split_whitespace();
+	let method = parts.next();
+	// Query strings are ignored
+	let path = parts
+		.next()
+		.map(|path| path.split('?').next().unwrap_or(path));
+	let response = match (method, path) {
+		(Some("GET"), Some(METRICS_PATH)) => get_http_response(
+			"200 OK",
+			METRICS_CONTENT_TYPE,
+			&data_handler::get_metrics().await,
+		),
+		(Some("GET"), _) => get_http_response("404 Not Found", "text/plain", "Not found\n"),
+		_ => get_http_response(
+			"405 Method Not Allowed",
+			"text/plain",
+			"Method not allowed\n",
+		),
+	};
+
+	let mut stream = &stream;
+	if let Err(err) = stream.write_all(response.as_bytes()).await {
+		logger::debug!("Error while writing metrics response: {}", err);
+	}
+}
+
+// Returns the request line. The headers don't matter, but they have to be read before responding
+async fn read_request(stream: &TcpStream) -> io::Result<String> {
+	let mut reader = BufReader::new(stream).take(MAX_REQUEST_SIZE);
+	let mut request_line = String::new();
+	reader.read_line(&mut request_line).await?;
+	loop {
+		let mut header = String::new();
+		match reader.read_line(&mut header).await? {
+			0 => break,
+			_ if header.trim().is_empty() => break,
+			_ => {}
+		}
+	}
+	if reader.limit() == 0 {
+		return Err(Error::new(
+			ErrorKind::InvalidData,
+			format!("the request is longer than {} bytes", MAX_REQUEST_SIZE),
+		));
+	}
+	Ok(request_line)
+}
+
+fn get_http_response(status: &str, content_type: &str, body: &str) -> String {
+	format!(
+		"HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+		status,
+		content_type,
+		body.len(),
+		body
+	)
+}
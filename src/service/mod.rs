@@ -1,65 +1,125 @@
-use crate::utils::{constants, logger};
+use crate::{
+	models::{juno_module, outbound_queue, Backpressure, Listener, ModuleComm},
+	utils::{constants, logger},
+};
+use juno::connection::Buffer;
 
-use async_std::{fs::remove_file, io::Result, net::TcpListener};
+use async_std::{
+	fs::remove_file,
+	io::{Error, ErrorKind, Result},
+	net::TcpListener,
+	task,
+};
 
 use fslock::LockFile;
+use futures::{channel::mpsc::unbounded, future};
 
 pub mod data_handler;
+pub mod dependency_graph;
+pub mod metrics;
+pub mod metrics_server;
 pub mod socket_server;
 
-pub async fn start(socket_path: &str) -> Result<()> {
+// Listens on every listener at once, until juno shuts down. If any of them
+// can't be listened on, none of them are
+pub async fn start(listeners: &[Listener]) -> Result<()> {
 	// Make sure no other instances of the application is running
-	if crate::get_connection_type() == constants::connection_types::UNIX_SOCKET {
-		let mut lock_file_path = socket_path.to_string();
-		lock_file_path.push_str(".lock");
+	let mut lock_files = vec![];
+	for listener in listeners.iter() {
+		let result = match listener {
+			Listener::Unix(socket_path) => lock_socket(socket_path).await.map(|lock_file| {
+				lock_files.push((socket_path.clone(), lock_file));
+			}),
+			Listener::Inet(address) => check_address(address).await,
+		};
+		if let Err(err) = result {
+			unlock_sockets(lock_files).await?;
+			return Err(err);
+		}
+	}
 
-		logger::verbose("Attempting to aquire lock file");
-		let mut file_lock = LockFile::open(&lock_file_path)?;
+	// Setup juno module, shared by every listener
+	let (read_data_sender, read_data_receiver) = unbounded::<Buffer>();
+	let (write_data_sender, write_data_receiver) = unbounded::<Buffer>();
+	task::spawn(async move {
+		// juno's own module is never held back by a full queue
+		let (sender, mut receiver) = outbound_queue::channel(0, Backpressure::Block);
+		let module_comm = ModuleComm::new_internal_comm(0, read_data_sender, sender);
 
-		if !file_lock.try_lock()? {
-			logger::error(&format!(
-				"Unable to aquire socket file lock. Are there any other instances of {} running?",
-				constants::APP_NAME
-			));
-			panic!("Exiting...");
-		};
-		logger::verbose("Lock file aquired.");
+		let read_future = module_comm.internal_read_loop(write_data_receiver);
+		let write_future = module_comm.write_data_loop(&mut receiver);
 
-		let socket_listener_result = socket_server::listen(&socket_path).await;
+		future::join(read_future, write_future).await;
+		logger::verbose("Disconnecting internal modules...");
+	});
+	let module = juno_module::setup_juno_module(read_data_receiver, write_data_sender).await;
 
-		logger::verbose("Socket server has finished executing. Unlocking lock file...");
+	// When one listener fails, the others are dropped along with it
+	let socket_listener_result =
+		future::try_join_all(listeners.iter().map(socket_server::listen)).await;
 
-		file_lock.unlock()?;
-		logger::verbose("Lock file unlocked. Removing lock file...");
+	drop(module);
 
-		remove_file(lock_file_path).await?;
-		logger::verbose("Lock file removed. Removing socket file...");
+	logger::verbose("Socket servers have finished executing. Unlocking lock files...");
+	unlock_sockets(lock_files).await?;
+
+	socket_listener_result?;
+	Ok(())
+}
+
+pub async fn on_exit() {
+	socket_server::on_exit().await;
+}
 
-		remove_file(socket_path).await?;
-		logger::verbose("Socket file removed.");
+async fn lock_socket(socket_path: &str) -> Result<LockFile> {
+	let mut lock_file_path = socket_path.to_string();
+	lock_file_path.push_str(".lock");
 
-		socket_listener_result?;
-	} else if crate::get_connection_type() == constants::connection_types::INET_SOCKET {
-		let result = TcpListener::bind(socket_path).await;
-		if result.is_err() {
-			logger::error(&format!(
-				"Unable to open port '{}'. Are there any other instances of {} running?",
-				socket_path,
+	logger::verbose!("Attempting to aquire lock file {}", lock_file_path);
+	let mut file_lock = LockFile::open(&lock_file_path)?;
+
+	if !file_lock.try_lock()? {
+		return Err(Error::new(
+			ErrorKind::AddrInUse,
+			format!(
+				"Unable to aquire socket file lock {}. Are there any other instances of {} running?",
+				lock_file_path,
 				constants::APP_NAME
-			));
-			panic!("Exiting...");
-		}
-		drop(result);
-		logger::verbose("Port is available");
+			),
+		));
+	};
+	logger::verbose("Lock file aquired.");
+	Ok(file_lock)
+}
 
-		socket_server::listen(&socket_path).await?;
-	} else {
-		panic!("Any other connection type other than INet sockets and Unix Sockets are not implemented yet");
+async fn check_address(address: &str) -> Result<()> {
+	if let Err(err) = TcpListener::bind(address).await {
+		return Err(Error::new(
+			err.kind(),
+			format!(
+				"Unable to open address '{}' ({}). Are there any other instances of {} running?",
+				address,
+				err,
+				constants::APP_NAME
+			),
+		));
 	}
-
+	logger::verbose!("Address {} is available", address);
 	Ok(())
 }
 
-pub async fn on_exit() {
-	socket_server::on_exit().await;
+async fn unlock_sockets(lock_files: Vec<(String, LockFile)>) -> Result<()> {
+	for (socket_path, mut file_lock) in lock_files {
+		file_lock.unlock()?;
+		logger::verbose("Lock file unlocked. Removing lock file...");
+
+		remove_file(format!("{}.lock", socket_path)).await?;
+		logger::verbose("Lock file removed. Removing socket file...");
+
+		// The socket isn't there if juno never got to listen on it
+		if remove_file(&socket_path).await.is_ok() {
+			logger::verbose("Socket file removed.");
+		}
+	}
+	Ok(())
 }
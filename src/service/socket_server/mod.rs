@@ -1,51 +1,48 @@
-use crate::constants;
+use crate::models::Listener;
 
-use async_std::io::Result;
+use async_std::{io::Result, sync::Mutex};
+
+use futures::channel::mpsc::{unbounded, UnboundedReceiver, UnboundedSender};
+use futures_util::sink::SinkExt;
 
 mod socket_server_inet;
 #[cfg(target_family = "unix")]
 mod socket_server_unix;
 
+lazy_static! {
+	// One for every listener, to stop it from accepting connections
+	static ref CLOSE_LISTENERS: Mutex<Vec<UnboundedSender<()>>> = Mutex::new(vec![]);
+}
+
 #[cfg(target_family = "windows")]
-pub async fn listen(socket_path: &str) -> Result<()> {
-	if crate::get_connection_type() == constants::connection_types::UNIX_SOCKET {
-		panic!("Unix sockets are not supported in windows. How did you even get here?");
-	} else if crate::get_connection_type() == constants::connection_types::INET_SOCKET {
-		socket_server_inet::listen(socket_path).await
-	} else {
-		panic!("Any other connection type other than INet sockets and Unix Sockets are not implemented yet");
+pub async fn listen(listener: &Listener) -> Result<()> {
+	match listener {
+		Listener::Unix(_) => {
+			panic!("Unix sockets are not supported in windows. How did you even get here?")
+		}
+		Listener::Inet(address) => socket_server_inet::listen(address).await,
 	}
 }
 
 #[cfg(target_family = "unix")]
-pub async fn listen(socket_path: &str) -> Result<()> {
-	if crate::get_connection_type() == constants::connection_types::UNIX_SOCKET {
-		socket_server_unix::listen(socket_path).await
-	} else if crate::get_connection_type() == constants::connection_types::INET_SOCKET {
-		socket_server_inet::listen(socket_path).await
-	} else {
-		panic!("Any other connection type other than INet sockets and Unix Sockets are not implemented yet");
+pub async fn listen(listener: &Listener) -> Result<()> {
+	match listener {
+		Listener::Unix(socket_path) => socket_server_unix::listen(socket_path).await,
+		Listener::Inet(address) => socket_server_inet::listen(address).await,
 	}
 }
 
-#[cfg(target_family = "windows")]
 pub async fn on_exit() {
-	if crate::get_connection_type() == constants::connection_types::UNIX_SOCKET {
-		panic!("Unix sockets are not supported in windows. How did you even get here?");
-	} else if crate::get_connection_type() == constants::connection_types::INET_SOCKET {
-		socket_server_inet::on_exit().await;
-	} else {
-		panic!("Any other connection type other than INet sockets and Unix Sockets are not implemented yet");
+	let close_senders = std::mem::take(&mut *CLOSE_LISTENERS.lock().await);
+	for mut sender in close_senders {
+		// The listener might've stopped already
+		let _ = sender.send(()).await;
 	}
 }
 
-#[cfg(target_family = "unix")]
-pub async fn on_exit() {
-	if crate::get_connection_type() == constants::connection_types::UNIX_SOCKET {
-		socket_server_unix::on_exit().await;
-	} else if crate::get_connection_type() == constants::connection_types::INET_SOCKET {
-		socket_server_inet::on_exit().await;
-	} else {
-		panic!("Any other connection type other than INet sockets and Unix Sockets are not implemented yet");
-	}
+// Receives once juno is shutting down
+async fn on_close() -> UnboundedReceiver<()> {
+	let (sender, receiver) = unbounded::<()>();
+	CLOSE_LISTENERS.lock().await.push(sender);
+	receiver
 }
@@ -1,9 +1,4 @@
-use crate::{
-	models::{juno_module, ModuleComm},
-	service::data_handler,
-	utils::logger,
-};
-use juno::connection::Buffer;
+use crate::{models::ModuleComm, service::data_handler, utils::logger};
 
 use async_std::{
 	fs::remove_file,
@@ -11,19 +6,10 @@ use async_std::{
 	os::unix::net::{UnixListener, UnixStream},
 	path::Path,
 	prelude::*,
-	sync::Mutex,
 	task,
 };
 
-use futures::{
-	channel::mpsc::{unbounded, UnboundedSender},
-	future::{self, Either},
-};
-use futures_util::sink::SinkExt;
-
-lazy_static! {
-	static ref CLOSE_LISTENER: Mutex<Option<UnboundedSender<()>>> = Mutex::new(None);
-}
+use futures::future::{self, Either};
 
 pub async fn listen(socket_path: &str) -> Result<()> {
 	let socket_path = Path::new(socket_path);
@@ -33,29 +19,16 @@ pub async fn listen(socket_path: &str) -> Result<()> {
 		remove_file(socket_path).await?;
 	}
 
-	let (sender, mut receiver) = unbounded::<()>();
-	CLOSE_LISTENER.lock().await.replace(sender);
+	let mut receiver = super::on_close().await;
 	let mut close_future = receiver.next();
 
 	let socket_server = UnixListener::bind(socket_path).await?;
 	let mut incoming = socket_server.incoming();
 
-	// Setup juno module
-	let (read_data_sender, read_data_receiver) = unbounded::<Buffer>();
-	let (write_data_sender, write_data_receiver) = unbounded::<Buffer>();
-	task::spawn(async move {
-		let (sender, mut receiver) = unbounded::<String>();
-		let module_comm = ModuleComm::new_internal_comm(0, read_data_sender, sender);
-
-		let read_future = module_comm.internal_read_loop(write_data_receiver);
-		let write_future = module_comm.write_data_loop(&mut receiver);
-
-		future::join(read_future, write_future).await;
-		logger::verbose("Disconnecting internal modules...");
-	});
-	let module = juno_module::setup_juno_module(read_data_receiver, write_data_sender).await;
-
-	logger::verbose("Listening for socket connections...");
+	logger::verbose!(
+		"Listening for socket connections on socket location {}...",
+		socket_path.display()
+	);
 	while let Either::Left((Some(stream), next_close_future)) =
 		future::select(incoming.next(), close_future).await
 	{
@@ -64,20 +37,11 @@ pub async fn listen(socket_path: &str) -> Result<()> {
 		task::spawn(handle_unix_socket_client(stream));
 	}
 
-	drop(module);
-
 	logger::verbose("Socket server is closed.");
 
 	Ok(())
 }
 
-pub async fn on_exit() {
-	let close_sender = CLOSE_LISTENER.lock().await.take();
-	if let Some(mut sender) = close_sender {
-		sender.send(()).await.unwrap();
-	}
-}
-
 async fn handle_unix_socket_client(stream: Result<UnixStream>) {
 	if stream.is_err() {
 		logger::error("Error occured while opening socket");
@@ -85,17 +49,25 @@ async fn handle_unix_socket_client(stream: Result<UnixStream>) {
 	}
 
 	let stream = stream.unwrap();
-	let (sender, mut receiver) = unbounded::<String>();
-	logger::verbose("New MPSC channel created");
+	let (sender, mut receiver) = data_handler::new_outbound_queue().await;
+	logger::verbose("New sending queue created");
 
 	let uuid = data_handler::new_connection_id().await;
-	logger::info(&format!("New connection assigned ID {}", uuid));
+	logger::info!("New connection assigned ID {}", uuid);
 	let module_comm = ModuleComm::new_unix_comm(uuid, stream, sender);
 
-	logger::verbose(&format!("Polling connection ID {}", uuid));
-	let read_future = module_comm.read_data_loop();
+	logger::verbose!("Polling connection ID {}", uuid);
+	// The connection isn't pinged anymore once it stops being read from
+	let (heartbeat_future, heartbeat_handle) =
+		future::abortable(data_handler::heartbeat_loop(&module_comm));
+	let read_future = async {
+		module_comm.read_data_loop().await;
+		heartbeat_handle.abort();
+	};
 	let write_future = module_comm.write_data_loop(&mut receiver);
 
-	future::join(read_future, write_future).await;
-	logger::info(&format!("Connection with ID {} disconnected", uuid));
+	// The heartbeat loop is expected to be aborted, so its result is of no use
+	let _ = future::join3(read_future, write_future, heartbeat_future).await;
+	data_handler::on_connection_closed(&uuid).await;
+	logger::info!("Connection with ID {} disconnected", uuid);
 }
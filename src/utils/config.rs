@@ -0,0 +1,603 @@
+use crate::{
+	models::Listener,
+	utils::constants::{self, backpressure, log_formats},
+};
+
+use std::{collections::HashMap, env, fs};
+
+use clap::ArgMatches;
+use toml::{Table, Value};
+
+// Every setting can be given on the command line, as an environment variable or in a TOML config
+// file (given with --config, or JUNO_CONFIG), in that order of precedence. The environment variable of a setting is its command line
+// flag in upper case, with a JUNO_ prefix (--log-file is JUNO_LOG_FILE). In the config file,
+// settings are grouped into tables:
+//
+// [[listeners]]
+// socket = "./juno.sock"
+//
+// [[listeners]]
+// port = 2203
+// bind_address = "::1"
+//
+// [logging]
+// level = "info"
+// filter = "module:billing=verbose"
+//
+// [timeouts]
+// function_call = 30000
+//
+// [modules]
+// policy = "policy.json"
+// backpressure = "dropOldest"
+//
+// Keys that aren't settings are rejected, so that a typo doesn't go unnoticed. Listeners are the
+// exception to the order of precedence: if any are given on the command line, those are the only
+// listeners, and otherwise the ones in the environment replace the ones in the file
+
+struct Setting {
+	// The command line flag, which is also what the setting is looked up by
+	flag: &'static str,
+	// <table>.<key> in the config file
+	key: &'static str,
+	kind: Kind,
+}
+
+enum Kind {
+	Text,
+	Number,
+	Flag,
+	Choice(&'static [&'static str]),
+}
+
+const LOG_LEVELS: &[&str] = &["verbose", "debug", "info", "warn", "error"];
+
+const SETTINGS: &[Setting] = &[
+	Setting {
+		flag: "log-level",
+		key: "logging.level",
+		kind: Kind::Choice(LOG_LEVELS),
+	},
+	Setting {
+		flag: "log-filter",
+		key: "logging.filter",
+		kind: Kind::Text,
+	},
+	Setting {
+		flag: "log-format",
+		key: "logging.format",
+		kind: Kind::Choice(&[log_formats::CONSOLE, log_formats::JSON]),
+	},
+	Setting {
+		flag: "log-file",
+		key: "logging.file",
+		kind: Kind::Text,
+	},
+	Setting {
+		flag: "log-max-size",
+		key: "logging.max_size",
+		kind: Kind::Number,
+	},
+	Setting {
+		flag: "log-rotate-daily",
+		key: "logging.rotate_daily",
+		kind: Kind::Flag,
+	},
+	Setting {
+		flag: "log-retention",
+		key: "logging.retention",
+		kind: Kind::Number,
+	},
+	Setting {
+		flag: "call-timeout",
+		key: "timeouts.function_call",
+		kind: Kind::Number,
+	},
+	Setting {
+		flag: "grace-period",
+		key: "timeouts.grace_period",
+		kind: Kind::Number,
+	},
+	Setting {
+		flag: "heartbeat-interval",
+		key: "timeouts.heartbeat_interval",
+		kind: Kind::Number,
+	},
+	Setting {
+		flag: "max-missed-heartbeats",
+		key: "timeouts.max_missed_heartbeats",
+		kind: Kind::Number,
+	},
+	Setting {
+		flag: "credentials",
+		key: "modules.credentials",
+		kind: Kind::Text,
+	},
+	Setting {
+		flag: "max-auth-failures",
+		key: "modules.max_auth_failures",
+		kind: Kind::Number,
+	},
+	Setting {
+		flag: "policy",
+		key: "modules.policy",
+		kind: Kind::Text,
+	},
+	Setting {
+		flag: "call-rate",
+		key: "modules.call_rate",
+		kind: Kind::Number,
+	},
+	Setting {
+		flag: "trigger-rate",
+		key: "modules.trigger_rate",
+		kind: Kind::Number,
+	},
+	Setting {
+		flag: "byte-rate",
+		key: "modules.byte_rate",
+		kind: Kind::Number,
+	},
+	Setting {
+		flag: "queue-limit",
+		key: "modules.queue_limit",
+		kind: Kind::Number,
+	},
+	Setting {
+		flag: "backpressure",
+		key: "modules.backpressure",
+		kind: Kind::Choice(&[
+			backpressure::BLOCK,
+			backpressure::DROP_OLDEST,
+			backpressure::DISCONNECT,
+		]),
+	},
+	Setting {
+		flag: "metrics-addr",
+		key: "metrics.address",
+		kind: Kind::Text,
+	},
+];
+
+// The settings in effect, by command line flag. Values are kept
+// as they would've been written on the command line
+#[derive(Default)]
+pub struct Config {
+	values: HashMap<&'static str, String>,
+	listeners: Vec<Listener>,
+}
+
+impl Config {
+	// Reads the config file (if any), then the environment and then the command line on top of it
+	pub fn load(args: &ArgMatches) -> Result<Self, String> {
+		let mut config = Config::default();
+		let path = args
+			.value_of("config")
+			.map(String::from)
+			.or_else(|| env::var(get_env_name("config")).ok());
+		if let Some(path) = path {
+			config.read_file(&path)?;
+		}
+		config.read_env()?;
+		config.read_args(args)?;
+		Ok(config)
+	}
+
+	pub fn get(&self, flag: &str) -> Option<&str> {
+		self.values.get(flag).map(String::as_str)
+	}
+
+	pub fn is_enabled(&self, flag: &str) -> bool {
+		self.get(flag) == Some("true")
+	}
+
+	// Empty if no listener was given anywhere
+	pub fn get_listeners(&self) -> &[Listener] {
+		&self.listeners
+	}
+
+	fn read_file(&mut self, path: &str) -> Result<(), String> {
+		let contents = fs::read_to_string(path)
+			.map_err(|err| format!("Unable to read config file {}: {}", path, err))?;
+		let table = contents.parse::<Table>().map_err(|err| {
+			format!(
+				"Invalid config file {}: {}",
+				path,
+				err.to_string().trim_end()
+			)
+		})?;
+
+		for (table_name, value) in table.iter() {
+			if table_name == "listeners" {
+				let listeners = match value {
+					Value::Array(listeners) => listeners,
+					_ => return Err(invalid_key(path, table_name, "must be an array of tables")),
+				};
+				for (index, listener) in listeners.iter().enumerate() {
+					let key = format!("{}[{}]", table_name, index);
+					self.listeners.push(read_listener(path, &key, listener)?);
+				}
+				continue;
+			}
+			let keys = match value {
+				Value::Table(keys) => keys,
+				_ if is_table(table_name) => {
+					return Err(invalid_key(path, table_name, "must be a table"));
+				}
+				_ => return Err(invalid_key(path, table_name, "is not a setting")),
+			};
+			for (key, value) in keys.iter() {
+				let key = format!("{}.{}", table_name, key);
+				let setting = SETTINGS.iter().find(|setting| setting.key == key);
+				if setting.is_none() {
+					return Err(invalid_key(path, &key, "is not a setting"));
+				}
+				let setting = setting.unwrap();
+				let value = from_toml(&setting.kind, value)
+					.map_err(|expected| invalid_key(path, &key, &expected))?;
+				self.values.insert(setting.flag, value);
+			}
+		}
+		Ok(())
+	}
+
+	fn read_env(&mut self) -> Result<(), String> {
+		for setting in SETTINGS.iter() {
+			let name = get_env_name(setting.flag);
+			if let Some(value) = get_env(&name)? {
+				let value = from_text(&setting.kind, value).map_err(|expected| {
+					format!("Invalid environment variable {}: {}", name, expected)
+				})?;
+				self.values.insert(setting.flag, value);
+			}
+		}
+
+		let mut listeners = vec![];
+		let name = get_env_name("listen");
+		if let Some(value) = get_env(&name)? {
+			for listener in value.split(',').map(str::trim) {
+				listeners.push(Listener::parse(listener).map_err(|error| {
+					format!("Invalid environment variable {}: {}", name, error)
+				})?);
+			}
+		}
+		let port = get_env(&get_env_name("port"))?;
+		if let Some(port) = &port {
+			if port.parse::<u16>().is_err() {
+				return Err(format!(
+					"Invalid environment variable {}: must be a port number",
+					get_env_name("port")
+				));
+			}
+		}
+		listeners.extend(get_listeners_from(
+			get_env(&get_env_name("socket-location"))?.as_deref(),
+			port.as_deref(),
+			get_env(&get_env_name("bind-addr"))?.as_deref(),
+		));
+		if !listeners.is_empty() {
+			self.listeners = listeners;
+		}
+		Ok(())
+	}
+
+	// Clap only checks the choices, so the rest is validated like the environment is
+	fn read_args(&mut self, args: &ArgMatches) -> Result<(), String> {
+		for setting in SETTINGS.iter() {
+			if let Kind::Flag = setting.kind {
+				if args.is_present(setting.flag) {
+					self.values.insert(setting.flag, String::from("true"));
+				}
+			} else if let Some(value) = args.value_of(setting.flag) {
+				let value = from_text(&setting.kind, String::from(value)).map_err(|expected| {
+					format!("Invalid argument --{}: {}", setting.flag, expected)
+				})?;
+				self.values.insert(setting.flag, value);
+			}
+		}
+
+		let mut listeners = vec![];
+		for listener in args.values_of("listen").into_iter().flatten() {
+			listeners.push(Listener::parse(listener)?);
+		}
+		listeners.extend(get_listeners_from(
+			args.value_of("socket-location"),
+			args.value_of("port"),
+			args.value_of("bind-addr"),
+		));
+		if !listeners.is_empty() {
+			self.listeners = listeners;
+		}
+		Ok(())
+	}
+}
+
+// The environment variable a setting is read from. Eg: JUNO_LOG_FILE
+pub fn get_env_name(flag: &str) -> String {
+	format!(
+		"{}{}",
+		constants::ENV_PREFIX,
+		flag.to_uppercase().replace('-', "_")
+	)
+}
+
+fn get_env(name: &str) -> Result<Option<String>, String> {
+	match env::var(name) {
+		Ok(value) => Ok(Some(value)),
+		Err(env::VarError::NotPresent) => Ok(None),
+		Err(env::VarError::NotUnicode(_)) => Err(format!(
+			"Invalid environment variable {}: not valid UTF-8",
+			name
+		)),
+	}
+}
+
+// A port (or a bind address) on its own listens on the default bind address (or port)
+fn get_listeners_from(
+	socket_location: Option<&str>,
+	port: Option<&str>,
+	bind_address: Option<&str>,
+) -> Vec<Listener> {
+	let mut listeners = vec![];
+	if let Some(socket_location) = socket_location {
+		listeners.push(Listener::Unix(String::from(socket_location)));
+	}
+	if port.is_some() || bind_address.is_some() {
+		listeners.push(Listener::from_port(
+			bind_address.unwrap_or(constants::DEFAULT_BIND_ADDRESS),
+			port.unwrap_or(constants::DEFAULT_PORT),
+		));
+	}
+	listeners
+}
+
+// A listener in the config file has either a socket, or a port and a bind address
+fn read_listener(path: &str, key: &str, value: &Value) -> Result<Listener, String> {
+	let keys = match value {
+		Value::Table(keys) => keys,
+		_ => return Err(invalid_key(path, key, "must be a table")),
+	};
+
+	let mut socket_location = None;
+	let mut port = None;
+	let mut bind_address = None;
+	for (name, value) in keys.iter() {
+		let name_key = format!("{}.{}", key, name);
+		match (name.as_str(), value) {
+			("socket", Value::String(value)) => socket_location = Some(value.clone()),
+			("port", Value::Integer(value)) if (0..=i64::from(u16::MAX)).contains(value) => {
+				port = Some(value.to_string())
+			}
+			("bind_address", Value::String(value)) => bind_address = Some(value.clone()),
+			("socket", _) | ("bind_address", _) => {
+				return Err(invalid_key(path, &name_key, "must be a string"));
+			}
+			("port", _) => return Err(invalid_key(path, &name_key, "must be a port number")),
+			_ => return Err(invalid_key(path, &name_key, "is not a setting")),
+		}
+	}
+
+	if socket_location.is_some() && (port.is_some() || bind_address.is_some()) {
+		return Err(invalid_key(
+			path,
+			&format!("{}.socket", key),
+			"can't be set along with 'port' or 'bind_address'",
+		));
+	}
+	get_listeners_from(
+		socket_location.as_deref(),
+		port.as_deref(),
+		bind_address.as_deref(),
+	)
+	.pop()
+	.ok_or_else(|| invalid_key(path, key, "must have a 'socket' or a 'port'"))
+}
+
+fn is_table(name: &str) -> bool {
+	SETTINGS
+		.iter()
+		.any(|setting| setting.key.split('.').next() == Some(name))
+}
+
+fn invalid_key(path: &str, key: &str, reason: &str) -> String {
+	format!("Invalid config file {}: '{}' {}", path, key, reason)
+}
+
+fn from_toml(kind: &Kind, value: &Value) -> Result<String, String> {
+	match (kind, value) {
+		(Kind::Text, Value::String(value)) => Ok(value.clone()),
+		(Kind::Number, Value::Integer(value)) if *value >= 0 => Ok(value.to_string()),
+		(Kind::Flag, Value::Boolean(value)) => Ok(value.to_string()),
+		(Kind::Choice(_), Value::String(value)) => from_text(kind, value.clone()),
+		_ => Err(get_expected(kind)),
+	}
+}
+
+fn from_text(kind: &Kind, value: String) -> Result<String, String> {
+	let valid = match kind {
+		Kind::Text => true,
+		Kind::Number => value.parse::<u64>().is_ok(),
+		Kind::Flag => value == "true" || value == "false",
+		Kind::Choice(choices) => choices.contains(&value.as_str()),
+	};
+	if valid {
+		Ok(value)
+	} else {
+		Err(get_expected(kind))
+	}
+}
+
+fn get_expected(kind: &Kind) -> String {
+	match kind {
+		Kind::Text => String::from("must be a string"),
+		Kind::Number => String::from("must be a number that isn't negative"),
+		Kind::Flag => String::from("must be true or false"),
+		Kind::Choice(choices) => format!("must be one of {}", choices.join(", ")),
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	use clap::{App, Arg};
+
+	fn write_file(name: &str, contents: &str) -> String {
+		let path = env::temp_dir().join(format!("juno-config-{}.toml", name));
+		fs::write(&path, contents).unwrap();
+		String::from(path.to_str().unwrap())
+	}
+
+	fn read(name: &str, contents: &str) -> Result<Config, String> {
+		let path = write_file(name, contents);
+		let mut config = Config::default();
+		let result = config.read_file(&path);
+		fs::remove_file(&path).unwrap();
+		result.map(|_| config)
+	}
+
+	fn get_args(args: &[&str]) -> ArgMatches<'static> {
+		App::new("juno")
+			.arg(Arg::with_name("config").long("config").takes_value(true))
+			.arg(Arg::with_name("port").long("port").takes_value(true))
+			.arg(
+				Arg::with_name("listen")
+					.long("listen")
+					.takes_value(true)
+					.multiple(true),
+			)
+			.arg(
+				Arg::with_name("call-timeout")
+					.long("call-timeout")
+					.takes_value(true),
+			)
+			.arg(Arg::with_name("log-rotate-daily").long("log-rotate-daily"))
+			.get_matches_from(["juno"].iter().chain(args.iter()))
+	}
+
+	#[test]
+	fn reads_file() {
+		let config = read(
+			"valid",
+			r#"
+			[[listeners]]
+			socket = "./juno.sock"
+
+			[[listeners]]
+			port = 2204
+			bind_address = "::1"
+
+			[logging]
+			level = "info"
+			rotate_daily = true
+
+			[timeouts]
+			function_call = 30000
+			"#,
+		)
+		.unwrap();
+
+		assert_eq!(config.get("log-level"), Some("info"));
+		assert!(config.is_enabled("log-rotate-daily"));
+		assert_eq!(config.get("call-timeout"), Some("30000"));
+		assert_eq!(config.get("log-file"), None);
+		assert!(
+			config.get_listeners() ==
+				[
+					Listener::Unix(String::from("./juno.sock")),
+					Listener::Inet(String::from("[::1]:2204"))
+				]
+		);
+	}
+
+	#[test]
+	fn rejects_invalid_files() {
+		let invalid = [
+			"[logging]\nlevel = 'loud'",
+			"[logging]\nlevle = 'info'",
+			"[logging]\nmax_size = -1",
+			"[logging]\nrotate_daily = 'yes'",
+			"[timeouts]\nfunction_call = '30s'",
+			"[metrics]\naddress = 9090",
+			"logging = 'info'",
+			"policy = 'policy.json'",
+			"[[listeners]]\nsocket = './juno.sock'\nport = 2204",
+			"[[listeners]]\nbind_address = 2204",
+			"[[listeners]]\nport = 65536",
+			"[[listeners]]\npath = './juno.sock'",
+			"[listeners]\nport = 2204",
+			"[listener]\nport = 2204",
+			"[logging",
+		];
+		for (index, contents) in invalid.iter().enumerate() {
+			let name = format!("invalid-{}", index);
+			assert!(read(&name, contents).is_err(), "{}", contents);
+		}
+
+		let mut config = Config::default();
+		assert!(config.read_file("/nonexistent/juno.toml").is_err());
+	}
+
+	// The only test that sets environment variables, since they're shared by every test
+	#[test]
+	fn args_override_env_which_overrides_file() {
+		let path = write_file(
+			"precedence",
+			r#"
+			[[listeners]]
+			port = 2204
+
+			[logging]
+			file = "juno.log"
+			retention = 3
+
+			[timeouts]
+			function_call = 1000
+			"#,
+		);
+		env::set_var("JUNO_LOG_RETENTION", "5");
+		env::set_var("JUNO_CALL_TIMEOUT", "2000");
+		env::set_var("JUNO_PORT", "2205");
+
+		let config = Config::load(&get_args(&["--config", &path])).unwrap();
+		assert_eq!(config.get("log-file"), Some("juno.log"));
+		assert_eq!(config.get("log-retention"), Some("5"));
+		assert_eq!(config.get("call-timeout"), Some("2000"));
+		assert!(config.get_listeners() == [Listener::from_port("127.0.0.1", "2205")]);
+
+		let config = Config::load(&get_args(&[
+			"--config",
+			&path,
+			"--call-timeout",
+			"3000",
+			"--listen",
+			"unix:./juno.sock",
+			"--port",
+			"2206",
+			"--log-rotate-daily",
+		]))
+		.unwrap();
+		assert_eq!(config.get("log-file"), Some("juno.log"));
+		assert_eq!(config.get("log-retention"), Some("5"));
+		assert_eq!(config.get("call-timeout"), Some("3000"));
+		assert!(config.is_enabled("log-rotate-daily"));
+		assert!(
+			config.get_listeners() ==
+				[
+					Listener::Unix(String::from("./juno.sock")),
+					Listener::from_port("127.0.0.1", "2206")
+				]
+		);
+
+		env::set_var("JUNO_CALL_TIMEOUT", "soon");
+		let result = Config::load(&get_args(&["--config", &path]));
+
+		env::remove_var("JUNO_LOG_RETENTION");
+		env::remove_var("JUNO_CALL_TIMEOUT");
+		env::remove_var("JUNO_PORT");
+		fs::remove_file(&path).unwrap();
+		assert!(result.is_err());
+
+		assert!(Config::load(&get_args(&["--call-timeout", "soon"])).is_err());
+	}
+}
@@ -6,6 +6,31 @@ pub const APP_AUTHORS: &str = crate_authors!();
 pub const APP_DESCRIPTION: &str = crate_description!();
 
 pub const DEFAULT_SOCKET_LOCATION: &str = "../juno.sock";
+pub const DEFAULT_PORT: &str = "2203";
+pub const DEFAULT_BIND_ADDRESS: &str = "127.0.0.1";
+// Listeners starting with this are unix sockets. Eg: unix:/run/juno.sock
+pub const UNIX_LISTENER_PREFIX: &str = "unix:";
+// In milliseconds
+pub const DEFAULT_FUNCTION_CALL_TIMEOUT: u64 = 30000;
+// In milliseconds. How long late responses to a timed out function call are recognized and dropped
+pub const TIMED_OUT_REQUEST_RETENTION: u64 = 300000;
+// In milliseconds. How long a disconnected module is kept around for it to resume its session.
+// Off by default, since it holds back the news of every disconnection for that long
+pub const DEFAULT_DISCONNECT_GRACE_PERIOD: u64 = 0;
+// How many times a connection can fail to authenticate before it is closed
+pub const DEFAULT_MAX_AUTHENTICATION_FAILURES: u32 = 3;
+// In milliseconds. How often the policy file is checked for changes
+pub const POLICY_RELOAD_INTERVAL: u64 = 5000;
+// How many messages can be waiting to be written to a connection
+pub const DEFAULT_QUEUE_LIMIT: usize = 1024;
+// How many pings in a row a connection can leave unanswered before it is closed
+pub const DEFAULT_MAX_MISSED_HEARTBEATS: u32 = 3;
+// How many rotated log files are kept around
+pub const DEFAULT_LOG_RETENTION: usize = 7;
+// How many logs can be waiting to be written before new ones are dropped
+pub const LOG_BUFFER_SIZE: usize = 8192;
+// Settings can be given as environment variables named with this prefix. Eg: JUNO_PORT
+pub const ENV_PREFIX: &str = "JUNO_";
 
 pub mod request_keys {
 	pub const TYPE: &str = "type";
@@ -13,6 +38,7 @@ pub mod request_keys {
 	pub const MODULE_ID: &str = "moduleId";
 	pub const VERSION: &str = "version";
 	pub const DEPENDENCIES: &str = "dependencies";
+	pub const OPTIONAL_DEPENDENCIES: &str = "optionalDependencies";
 	pub const ERROR: &str = "error";
 	pub const FUNCTION: &str = "function";
 	pub const HOOK: &str = "hook";
@@ -20,6 +46,29 @@ pub mod request_keys {
 	pub const DATA: &str = "data";
 	pub const CALLER: &str = "caller";
 	pub const CONNECTION_ID: &str = "connectionId";
+	pub const TIMEOUT: &str = "timeout";
+	pub const REPLICA: &str = "replica";
+	pub const STICKY: &str = "sticky";
+	pub const ARGUMENTS_SCHEMA: &str = "argumentsSchema";
+	pub const RESPONSE_SCHEMA: &str = "responseSchema";
+	pub const FIELDS: &str = "fields";
+	pub const FIELD: &str = "field";
+	pub const REASON: &str = "reason";
+	pub const MESSAGE: &str = "message";
+	pub const REQUIRED: &str = "required";
+	pub const PRESENT: &str = "present";
+	pub const CYCLE: &str = "cycle";
+	pub const UNMET_DEPENDENCIES: &str = "unmetDependencies";
+	pub const ACTIVATED: &str = "activated";
+	pub const UPGRADE: &str = "upgrade";
+	pub const DEPENDENTS: &str = "dependents";
+	pub const PREVIOUS_VERSION: &str = "previousVersion";
+	pub const RESUMPTION_TOKEN: &str = "resumptionToken";
+	pub const TOKEN: &str = "token";
+	pub const LIMIT: &str = "limit";
+	pub const RETRY_AFTER: &str = "retryAfter";
+	pub const BACKPRESSURE: &str = "backpressure";
+	pub const TRACE: &str = "trace";
 }
 
 pub mod juno_hooks {
@@ -29,6 +78,42 @@ pub mod juno_hooks {
 	pub const MODULE_DEACTIVATED: &str = "moduleDeactivated";
 	pub const MODULE_CONNECTED: &str = "moduleConnected";
 	pub const MODULE_DISCONNECTED: &str = "moduleDisconnected";
+	pub const DEPENDENCIES_UNMET: &str = "dependenciesUnmet";
+	pub const OPTIONAL_DEPENDENCY_ACTIVATED: &str = "optionalDependencyActivated";
+	pub const OPTIONAL_DEPENDENCY_DEACTIVATED: &str = "optionalDependencyDeactivated";
+	pub const MODULE_UPGRADED: &str = "moduleUpgraded";
+}
+
+pub mod dependency_reasons {
+	pub const MISSING: &str = "missing";
+	pub const VERSION_MISMATCH: &str = "versionMismatch";
+	pub const CYCLE: &str = "cycle";
+	pub const DEPENDENCY_UNMET: &str = "dependencyUnmet";
+}
+
+pub mod rate_limits {
+	pub const CALLS: &str = "calls";
+	pub const TRIGGERS: &str = "triggers";
+	pub const BYTES: &str = "bytes";
+}
+
+pub mod backpressure {
+	pub const BLOCK: &str = "block";
+	pub const DROP_OLDEST: &str = "dropOldest";
+	pub const DISCONNECT: &str = "disconnect";
+}
+
+pub mod log_formats {
+	pub const CONSOLE: &str = "console";
+	pub const JSON: &str = "json";
+}
+
+pub mod log_fields {
+	pub const MODULE_ID: &str = "module_id";
+	pub const CONNECTION_ID: &str = "connection_id";
+	pub const REQUEST_ID: &str = "request_id";
+	pub const FUNCTION: &str = "function";
+	pub const HOOK: &str = "hook";
 }
 
 pub mod request_types {
@@ -48,6 +133,15 @@ pub mod request_types {
 
 	pub const DECLARE_FUNCTION_REQUEST: u64 = 9;
 	pub const DECLARE_FUNCTION_RESPONSE: u64 = 10;
+
+	pub const UNREGISTER_HOOK_REQUEST: u64 = 11;
+	pub const UNREGISTER_HOOK_RESPONSE: u64 = 12;
+
+	pub const UNDECLARE_FUNCTION_REQUEST: u64 = 13;
+	pub const UNDECLARE_FUNCTION_RESPONSE: u64 = 14;
+
+	pub const PING: u64 = 15;
+	pub const PONG: u64 = 16;
 }
 
 pub mod errors {
@@ -60,9 +154,12 @@ pub mod errors {
 	pub const UNKNOWN_FUNCTION: u32 = 5;
 	pub const INVALID_MODULE_ID: u32 = 6;
 	pub const DUPLICATE_MODULE: u32 = 7;
-}
-
-pub mod connection_types {
-	pub const UNIX_SOCKET: u8 = 1;
-	pub const INET_SOCKET: u8 = 2;
+	pub const TIMEOUT: u32 = 8;
+	pub const MODULE_DISCONNECTED: u32 = 9;
+	pub const INVALID_ARGUMENTS: u32 = 10;
+	pub const INCOMPATIBLE_UPGRADE: u32 = 11;
+	pub const INVALID_RESUMPTION_TOKEN: u32 = 12;
+	pub const UNAUTHORIZED: u32 = 13;
+	pub const FORBIDDEN: u32 = 14;
+	pub const RATE_LIMITED: u32 = 15;
 }
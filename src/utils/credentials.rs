@@ -0,0 +1,166 @@
+use crate::utils::wildcard;
+
+use sha2::{Digest, Sha256};
+use std::fs;
+
+use serde_json::Value;
+
+// Credentials files are JSON objects, mapping moduleIds to the token the module has to register with.
+// A moduleId can be a pattern, where a '*' matches any number of characters. If the token starts
+// with "sha256:", the rest of it is the hex-encoded SHA-256 hash of the token instead of the token itself.
+// When more than one pattern matches a moduleId, the most specific one (with the most characters
+// that aren't a '*') is used.
+
+const SHA256_PREFIX: &str = "sha256:";
+
+pub struct Credential {
+	pub pattern: String,
+	pub secret: String,
+	pub hashed: bool,
+}
+
+pub fn load(path: &str) -> Result<Vec<Credential>, String> {
+	let contents = fs::read_to_string(path)
+		.map_err(|error| format!("Unable to read credentials file '{}': {}", path, error))?;
+	let credentials: Value = serde_json::from_str(&contents)
+		.map_err(|error| format!("Credentials file '{}' is not valid JSON: {}", path, error))?;
+	let credentials = credentials
+		.as_object()
+		.ok_or_else(|| format!("Credentials file '{}' is not a JSON object", path))?;
+
+	let mut result = vec![];
+	for (pattern, secret) in credentials {
+		let secret = secret.as_str().ok_or_else(|| {
+			format!(
+				"The token for '{}' in credentials file '{}' is not a string",
+				pattern, path
+			)
+		})?;
+		let credential = if let Some(hash) = secret.strip_prefix(SHA256_PREFIX) {
+			if hash.len() != 64 || !hash.chars().all(|c| c.is_ascii_hexdigit()) {
+				return Err(format!(
+					"The token hash for '{}' in credentials file '{}' is not a hex-encoded SHA-256 hash",
+					pattern, path
+				));
+			}
+			Credential {
+				pattern: pattern.clone(),
+				secret: hash.to_ascii_lowercase(),
+				hashed: true,
+			}
+		} else {
+			Credential {
+				pattern: pattern.clone(),
+				secret: String::from(secret),
+				hashed: false,
+			}
+		};
+		result.push(credential);
+	}
+	Ok(result)
+}
+
+// Checks if the token is valid for the given moduleId. moduleIds without any credentials are never authorized
+pub fn is_authorized(credentials: &[Credential], module_id: &str, token: &str) -> bool {
+	let credential = credentials
+		.iter()
+		.filter(|credential| wildcard::matches(&credential.pattern, module_id))
+		.max_by_key(|credential| wildcard::get_specificity(&credential.pattern));
+
+	match credential {
+		Some(credential) if credential.hashed => {
+			let hash: String = Sha256::digest(token.as_bytes())
+				.iter()
+				.map(|byte| format!("{:02x}", byte))
+				.collect();
+			constant_time_eq(credential.secret.as_bytes(), hash.as_bytes())
+		}
+		Some(credential) => constant_time_eq(credential.secret.as_bytes(), token.as_bytes()),
+		None => false,
+	}
+}
+
+// Compares the two without returning early, so that the time taken doesn't reveal the secret
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+	if a.len() != b.len() {
+		return false;
+	}
+	a.iter().zip(b.iter()).fold(0, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	const SECRET_HASH: &str = "2bb80d537b1da3e38bd30361aa855686bde0eacd7162fef6a25fe97bf527a25b";
+
+	fn load_json(name: &str, contents: &str) -> Result<Vec<Credential>, String> {
+		let path = std::env::temp_dir().join(format!("juno-credentials-{}.json", name));
+		fs::write(&path, contents).unwrap();
+		let credentials = load(path.to_str().unwrap());
+		fs::remove_file(&path).unwrap();
+		credentials
+	}
+
+	fn credential(pattern: &str, secret: &str, hashed: bool) -> Credential {
+		Credential {
+			pattern: String::from(pattern),
+			secret: String::from(secret),
+			hashed,
+		}
+	}
+
+	#[test]
+	fn loads_credentials() {
+		let credentials = load_json(
+			"valid",
+			&format!(
+				r#"{{ "users": "secret", "billing-*": "sha256:{}" }}"#,
+				SECRET_HASH.to_ascii_uppercase()
+			),
+		)
+		.unwrap();
+		assert_eq!(credentials.len(), 2);
+		let hashed = credentials
+			.iter()
+			.find(|credential| credential.pattern == "billing-*")
+			.unwrap();
+		assert!(hashed.hashed);
+		assert_eq!(hashed.secret, SECRET_HASH);
+	}
+
+	#[test]
+	fn rejects_invalid_files() {
+		assert!(load("/nonexistent/juno-credentials.json").is_err());
+		assert!(load_json("not-json", "users").is_err());
+		assert!(load_json("not-an-object", r#"["users"]"#).is_err());
+		assert!(load_json("not-a-string", r#"{ "users": 1 }"#).is_err());
+		assert!(load_json("bad-hash", r#"{ "users": "sha256:1234" }"#).is_err());
+	}
+
+	#[test]
+	fn authorizes_tokens() {
+		let credentials = vec![
+			credential("users", "secret", false),
+			credential("billing", SECRET_HASH, true),
+		];
+		assert!(is_authorized(&credentials, "users", "secret"));
+		assert!(!is_authorized(&credentials, "users", "secrets"));
+		assert!(is_authorized(&credentials, "billing", "secret"));
+		assert!(!is_authorized(&credentials, "billing", SECRET_HASH));
+		assert!(!is_authorized(&credentials, "admins", "secret"));
+	}
+
+	#[test]
+	fn prefers_the_most_specific_pattern() {
+		let credentials = vec![
+			credential("*", "anything", false),
+			credential("billing-*", "billing", false),
+			credential("billing-eu", "eu", false),
+		];
+		assert!(is_authorized(&credentials, "billing-eu", "eu"));
+		assert!(!is_authorized(&credentials, "billing-eu", "billing"));
+		assert!(is_authorized(&credentials, "billing-us", "billing"));
+		assert!(is_authorized(&credentials, "users", "anything"));
+	}
+}
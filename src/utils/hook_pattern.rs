@@ -0,0 +1,71 @@
+// A hook pattern is of the format 'module-name.hook-name', where either part can be a '*'
+// to match any module or any hook. Eg: 'users.*', '*.passwordChanged' or '*.*'
+const WILDCARD: &str = "*";
+
+pub fn is_valid(pattern: &str) -> bool {
+	let parts = split(pattern);
+	if parts.is_none() {
+		return false;
+	}
+	let (module_part, hook_part) = parts.unwrap();
+
+	is_valid_part(module_part) && is_valid_part(hook_part)
+}
+
+pub fn matches(pattern: &str, hook_name: &str) -> bool {
+	if pattern == hook_name {
+		return true;
+	}
+
+	let pattern_parts = split(pattern);
+	let hook_parts = split(hook_name);
+	if pattern_parts.is_none() || hook_parts.is_none() {
+		return false;
+	}
+	let (module_pattern, hook_pattern) = pattern_parts.unwrap();
+	let (module_name, hook_name) = hook_parts.unwrap();
+
+	(module_pattern == WILDCARD || module_pattern == module_name) &&
+		(hook_pattern == WILDCARD || hook_pattern == hook_name)
+}
+
+fn split(pattern: &str) -> Option<(&str, &str)> {
+	let index = pattern.find('.')?;
+	Some((&pattern[..index], &pattern[index + 1..]))
+}
+
+fn is_valid_part(part: &str) -> bool {
+	// A wildcard can only be used in place of an entire part
+	!part.is_empty() && (part == WILDCARD || !part.contains(WILDCARD))
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn validates_patterns() {
+		assert!(is_valid("users.passwordChanged"));
+		assert!(is_valid("users.*"));
+		assert!(is_valid("*.passwordChanged"));
+		assert!(is_valid("*.*"));
+
+		assert!(!is_valid("users"));
+		assert!(!is_valid(".passwordChanged"));
+		assert!(!is_valid("users."));
+		assert!(!is_valid("us*rs.passwordChanged"));
+		assert!(!is_valid("users.password*"));
+	}
+
+	#[test]
+	fn matches_hook_names() {
+		assert!(matches("users.passwordChanged", "users.passwordChanged"));
+		assert!(matches("users.*", "users.passwordChanged"));
+		assert!(matches("*.passwordChanged", "admins.passwordChanged"));
+		assert!(matches("*.*", "juno.moduleActivated"));
+
+		assert!(!matches("users.*", "admins.passwordChanged"));
+		assert!(!matches("*.passwordChanged", "users.loggedIn"));
+		assert!(!matches("*.*", "users"));
+	}
+}
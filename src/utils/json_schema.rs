@@ -0,0 +1,316 @@
+use serde_json::Value;
+
+// A minimal JSON Schema validator. Supports the keywords that are useful for describing
+// function arguments: type, enum, const, properties, required, additionalProperties, items,
+// minimum, maximum, minLength, maxLength, minItems and maxItems. Other keywords are ignored.
+
+const TYPES: [&str; 7] = [
+	"null", "boolean", "object", "array", "number", "integer", "string",
+];
+
+pub struct ValidationError {
+	pub field: String,
+	pub reason: String,
+}
+
+pub fn is_valid_schema(schema: &Value) -> bool {
+	match schema {
+		Value::Bool(_) => true,
+		Value::Object(schema) => {
+			if let Some(r#type) = schema.get("type") {
+				let is_valid_type = match r#type {
+					Value::String(r#type) => TYPES.contains(&r#type.as_str()),
+					Value::Array(types) => types.iter().all(
+						|r#type| matches!(r#type.as_str(), Some(r#type) if TYPES.contains(&r#type)),
+					),
+					_ => false,
+				};
+				if !is_valid_type {
+					return false;
+				}
+			}
+			if let Some(properties) = schema.get("properties") {
+				if !properties.is_object() ||
+					!properties
+						.as_object()
+						.unwrap()
+						.values()
+						.all(is_valid_schema)
+				{
+					return false;
+				}
+			}
+			if let Some(items) = schema.get("items") {
+				if !is_valid_schema(items) {
+					return false;
+				}
+			}
+			if let Some(additional_properties) = schema.get("additionalProperties") {
+				if !is_valid_schema(additional_properties) {
+					return false;
+				}
+			}
+			if let Some(required) = schema.get("required") {
+				if !required.is_array() ||
+					!required.as_array().unwrap().iter().all(Value::is_string)
+				{
+					return false;
+				}
+			}
+			true
+		}
+		_ => false,
+	}
+}
+
+pub fn validate(schema: &Value, value: &Value, field: &str) -> Vec<ValidationError> {
+	let mut errors = vec![];
+	validate_value(schema, value, field, &mut errors);
+	errors
+}
+
+fn validate_value(schema: &Value, value: &Value, field: &str, errors: &mut Vec<ValidationError>) {
+	let schema = match schema {
+		Value::Bool(true) => return,
+		Value::Object(schema) => schema,
+		_ => {
+			add_error(errors, field, String::from("is not allowed"));
+			return;
+		}
+	};
+
+	if let Some(r#type) = schema.get("type") {
+		let matches = match r#type {
+			Value::String(r#type) => is_of_type(value, r#type),
+			Value::Array(types) => types
+				.iter()
+				.filter_map(Value::as_str)
+				.any(|r#type| is_of_type(value, r#type)),
+			_ => true,
+		};
+		if !matches {
+			add_error(errors, field, format!("should be of type {}", r#type));
+			// None of the other keywords make sense if the type is wrong
+			return;
+		}
+	}
+
+	if let Some(Value::Array(values)) = schema.get("enum") {
+		if !values.contains(value) {
+			add_error(
+				errors,
+				field,
+				format!("should be one of {}", Value::Array(values.clone())),
+			);
+		}
+	}
+	if let Some(constant) = schema.get("const") {
+		if constant != value {
+			add_error(errors, field, format!("should be {}", constant));
+		}
+	}
+
+	match value {
+		Value::Number(number) => {
+			let number = number.as_f64().unwrap_or_default();
+			if let Some(minimum) = schema.get("minimum").and_then(Value::as_f64) {
+				if number < minimum {
+					add_error(errors, field, format!("should be at least {}", minimum));
+				}
+			}
+			if let Some(maximum) = schema.get("maximum").and_then(Value::as_f64) {
+				if number > maximum {
+					add_error(errors, field, format!("should be at most {}", maximum));
+				}
+			}
+		}
+		Value::String(string) => {
+			let length = string.chars().count() as u64;
+			if let Some(min_length) = schema.get("minLength").and_then(Value::as_u64) {
+				if length < min_length {
+					add_error(
+						errors,
+						field,
+						format!("should have at least {} characters", min_length),
+					);
+				}
+			}
+			if let Some(max_length) = schema.get("maxLength").and_then(Value::as_u64) {
+				if length > max_length {
+					add_error(
+						errors,
+						field,
+						format!("should have at most {} characters", max_length),
+					);
+				}
+			}
+		}
+		Value::Array(items) => {
+			let length = items.len() as u64;
+			if let Some(min_items) = schema.get("minItems").and_then(Value::as_u64) {
+				if length < min_items {
+					add_error(
+						errors,
+						field,
+						format!("should have at least {} items", min_items),
+					);
+				}
+			}
+			if let Some(max_items) = schema.get("maxItems").and_then(Value::as_u64) {
+				if length > max_items {
+					add_error(
+						errors,
+						field,
+						format!("should have at most {} items", max_items),
+					);
+				}
+			}
+			if let Some(item_schema) = schema.get("items") {
+				for (index, item) in items.iter().enumerate() {
+					validate_value(item_schema, item, &format!("{}[{}]", field, index), errors);
+				}
+			}
+		}
+		Value::Object(object) => {
+			if let Some(Value::Array(required)) = schema.get("required") {
+				for key in required.iter().filter_map(Value::as_str) {
+					if !object.contains_key(key) {
+						add_error(
+							errors,
+							&format!("{}.{}", field, key),
+							String::from("is required"),
+						);
+					}
+				}
+			}
+			let properties = schema.get("properties").and_then(Value::as_object);
+			for (key, property) in object {
+				let property_field = format!("{}.{}", field, key);
+				if let Some(property_schema) = properties.and_then(|properties| properties.get(key))
+				{
+					validate_value(property_schema, property, &property_field, errors);
+				} else if let Some(additional_properties) = schema.get("additionalProperties") {
+					validate_value(additional_properties, property, &property_field, errors);
+				}
+			}
+		}
+		_ => {}
+	}
+}
+
+fn is_of_type(value: &Value, r#type: &str) -> bool {
+	match r#type {
+		"null" => value.is_null(),
+		"boolean" => value.is_boolean(),
+		"object" => value.is_object(),
+		"array" => value.is_array(),
+		"number" => value.is_number(),
+		// 1.0 is as much of an integer as 1
+		"integer" => value.as_f64().is_some_and(|number| number.fract() == 0.0),
+		"string" => value.is_string(),
+		_ => false,
+	}
+}
+
+fn add_error(errors: &mut Vec<ValidationError>, field: &str, reason: String) {
+	errors.push(ValidationError {
+		field: String::from(field),
+		reason,
+	});
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use serde_json::json;
+
+	fn errors_of(schema: Value, value: Value) -> Vec<(String, String)> {
+		validate(&schema, &value, "args")
+			.into_iter()
+			.map(|error| (error.field, error.reason))
+			.collect()
+	}
+
+	#[test]
+	fn validates_schemas() {
+		assert!(is_valid_schema(&json!(true)));
+		assert!(is_valid_schema(&json!({ "type": "integer" })));
+		assert!(is_valid_schema(&json!({ "type": ["string", "null"] })));
+		assert!(is_valid_schema(&json!({
+			"type": "object",
+			"properties": { "name": { "type": "string" } },
+			"required": ["name"],
+			"additionalProperties": false
+		})));
+
+		assert!(!is_valid_schema(&json!("string")));
+		assert!(!is_valid_schema(&json!({ "type": "text" })));
+		assert!(!is_valid_schema(&json!({ "type": ["string", "text"] })));
+		assert!(!is_valid_schema(&json!({ "type": 1 })));
+		assert!(!is_valid_schema(&json!({ "properties": { "name": 1 } })));
+		assert!(!is_valid_schema(&json!({ "required": "name" })));
+	}
+
+	#[test]
+	fn checks_types() {
+		assert!(errors_of(json!({ "type": "integer" }), json!(1)).is_empty());
+		assert!(errors_of(json!({ "type": "integer" }), json!(1.0)).is_empty());
+		assert!(!errors_of(json!({ "type": "integer" }), json!(1.5)).is_empty());
+		assert!(errors_of(json!({ "type": ["string", "null"] }), json!(null)).is_empty());
+		assert_eq!(
+			errors_of(json!({ "type": "string" }), json!(1)),
+			vec![(
+				String::from("args"),
+				String::from("should be of type \"string\"")
+			)]
+		);
+	}
+
+	#[test]
+	fn checks_bounds() {
+		let schema = json!({ "minimum": 1, "maximum": 3 });
+		assert!(errors_of(schema.clone(), json!(2)).is_empty());
+		assert!(!errors_of(schema.clone(), json!(0)).is_empty());
+		assert!(!errors_of(schema, json!(4)).is_empty());
+
+		let schema = json!({ "minLength": 2, "maxLength": 3 });
+		assert!(errors_of(schema.clone(), json!("äö")).is_empty());
+		assert!(!errors_of(schema, json!("a")).is_empty());
+
+		let schema = json!({ "items": { "type": "number" }, "maxItems": 2 });
+		assert_eq!(
+			errors_of(schema.clone(), json!([1, "2"])),
+			vec![(
+				String::from("args[1]"),
+				String::from("should be of type \"number\"")
+			)]
+		);
+		assert!(!errors_of(schema, json!([1, 2, 3])).is_empty());
+	}
+
+	#[test]
+	fn checks_objects() {
+		let schema = json!({
+			"properties": { "name": { "type": "string" } },
+			"required": ["name"],
+			"additionalProperties": false
+		});
+		assert!(errors_of(schema.clone(), json!({ "name": "juno" })).is_empty());
+		assert_eq!(
+			errors_of(schema.clone(), json!({})),
+			vec![(String::from("args.name"), String::from("is required"))]
+		);
+		assert_eq!(
+			errors_of(schema, json!({ "name": "juno", "age": 1 })),
+			vec![(String::from("args.age"), String::from("is not allowed"))]
+		);
+	}
+
+	#[test]
+	fn checks_enums_and_constants() {
+		assert!(errors_of(json!({ "enum": ["a", "b"] }), json!("a")).is_empty());
+		assert!(!errors_of(json!({ "enum": ["a", "b"] }), json!("c")).is_empty());
+		assert!(errors_of(json!({ "const": 1 }), json!(1)).is_empty());
+		assert!(!errors_of(json!({ "const": 1 }), json!(2)).is_empty());
+	}
+}
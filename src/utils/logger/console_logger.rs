@@ -1,34 +1,57 @@
 use colored::*;
 
-use super::{LogLevel, Logger};
+use super::{Fields, LogLevel, Logger, Record};
 
-pub struct ConsoleLogger {
-	verbosity: LogLevel,
-}
+pub struct ConsoleLogger;
 
 impl ConsoleLogger {
-	pub fn new(verbosity: LogLevel) -> Self {
-		ConsoleLogger { verbosity }
-	}
-
-	pub fn set_verbosity(&mut self, verbosity: LogLevel) {
-		self.verbosity = verbosity;
+	pub fn new() -> Self {
+		ConsoleLogger
 	}
 }
 
 impl Logger for ConsoleLogger {
-	fn write(&self, log_level: LogLevel, data: &str) {
-		let myself = self.verbosity as u8;
-		let other = log_level as u8;
-		if myself <= other {
-			let log_level = match log_level {
-				LogLevel::Verbose => log_level.to_string().green(),
-				LogLevel::Info => log_level.to_string().blue(),
-				LogLevel::Debug => log_level.to_string().yellow(),
-				LogLevel::Warn => log_level.to_string().on_yellow().black(),
-				LogLevel::Error => log_level.to_string().on_red().white(),
-			};
-			println!("[{}]: {}", log_level, data);
+	fn write(&mut self, record: &Record) {
+		let log_level = record.log_level;
+		let log_level = match log_level {
+			LogLevel::Verbose => log_level.to_string().green(),
+			LogLevel::Info => log_level.to_string().blue(),
+			LogLevel::Debug => log_level.to_string().yellow(),
+			LogLevel::Warn => log_level.to_string().on_yellow().black(),
+			LogLevel::Error => log_level.to_string().on_red().white(),
+		};
+		if record.fields.is_empty() {
+			println!("[{}]: {}", log_level, record.data);
+		} else {
+			println!(
+				"[{}]: {} {}",
+				log_level,
+				record.data,
+				format_fields(&record.fields).dimmed()
+			);
 		}
 	}
 }
+
+// The same as what's printed to the console, without the colors
+pub fn format_line(record: &Record) -> String {
+	if record.fields.is_empty() {
+		format!("[{}]: {}", record.log_level.to_string(), record.data)
+	} else {
+		format!(
+			"[{}]: {} {}",
+			record.log_level.to_string(),
+			record.data,
+			format_fields(&record.fields)
+		)
+	}
+}
+
+fn format_fields(fields: &Fields) -> String {
+	let fields = fields
+		.iter()
+		.map(|(key, value)| format!("{}={}", key, value))
+		.collect::<Vec<String>>()
+		.join(" ");
+	format!("({})", fields)
+}
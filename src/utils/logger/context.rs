@@ -0,0 +1,50 @@
+use super::Fields;
+
+use std::{
+	cell::RefCell,
+	future::Future,
+	pin::Pin,
+	sync::Arc,
+	task::{Context, Poll},
+};
+
+thread_local! {
+	// The fields of the future that's being polled on this thread, if any
+	static CURRENT: RefCell<Option<Arc<Fields>>> = const { RefCell::new(None) };
+}
+
+// Runs a future with fields that are added to every log made while it runs,
+// so that they don't have to be passed along to every log
+pub struct WithContext<F: Future> {
+	fields: Arc<Fields>,
+	future: Pin<Box<F>>,
+}
+
+impl<F: Future> WithContext<F> {
+	// A context inside another one has the fields of both
+	pub fn new(fields: Fields, future: F) -> Self {
+		let fields = match get_current() {
+			Some(outer) => fields.merged_with(&outer),
+			None => fields,
+		};
+		WithContext {
+			fields: Arc::new(fields),
+			future: Box::pin(future),
+		}
+	}
+}
+
+impl<F: Future> Future for WithContext<F> {
+	type Output = F::Output;
+
+	fn poll(mut self: Pin<&mut Self>, context: &mut Context<'_>) -> Poll<F::Output> {
+		let previous = CURRENT.with(|current| current.replace(Some(self.fields.clone())));
+		let result = self.future.as_mut().poll(context);
+		CURRENT.with(|current| *current.borrow_mut() = previous);
+		result
+	}
+}
+
+pub fn get_current() -> Option<Arc<Fields>> {
+	CURRENT.with(|current| current.borrow().clone())
+}
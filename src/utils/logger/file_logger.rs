@@ -0,0 +1,257 @@
+use super::{console_logger, json_logger, Logger, Record};
+
+use std::{
+	fs::{self, File, OpenOptions},
+	io::{self, BufWriter, Write},
+};
+
+use chrono::{NaiveDate, SecondsFormat, Utc};
+
+// Where the logs are written, and when the file is rotated. Rotated files are
+// renamed to <path>.1, <path>.2 and so on, with <path>.1 being the most recent
+pub struct LogFile {
+	pub path: String,
+	// In bytes. The file is rotated once it would grow past this. 0 doesn't rotate by size
+	pub max_size: u64,
+	// Rotates the file when the day changes (in UTC)
+	pub daily: bool,
+	// How many rotated files are kept around
+	pub retention: usize,
+}
+
+pub struct FileLogger {
+	log_file: LogFile,
+	json: bool,
+	file: BufWriter<File>,
+	size: u64,
+	opened_on: NaiveDate,
+}
+
+impl FileLogger {
+	pub fn new(json: bool, log_file: LogFile) -> io::Result<Self> {
+		let file = open(&log_file.path)?;
+
+		#[cfg(unix)]
+		reopen_on_hangup()?;
+
+		Ok(FileLogger {
+			size: file.metadata()?.len(),
+			file: BufWriter::new(file),
+			opened_on: Utc::now().date_naive(),
+			log_file,
+			json,
+		})
+	}
+
+	fn write_line(&mut self, line: &str) -> io::Result<()> {
+		let length = line.len() as u64 + 1;
+		let too_big = self.log_file.max_size > 0 &&
+			self.size > 0 &&
+			self.size + length > self.log_file.max_size;
+		let new_day = self.log_file.daily && Utc::now().date_naive() != self.opened_on;
+		if too_big || new_day {
+			if let Err(err) = self.rotate() {
+				// Losing the logs is worse than a file that's too big. Keep writing to the current
+				// file, and try again once it has grown by as much again, or the day changes
+				eprintln!("Unable to rotate log file: {}", err);
+				self.size = 0;
+				self.opened_on = Utc::now().date_naive();
+			}
+		}
+
+		writeln!(self.file, "{}", line)?;
+		self.size += length;
+		Ok(())
+	}
+
+	fn rotate(&mut self) -> io::Result<()> {
+		self.file.flush()?;
+		let path = &self.log_file.path;
+		let retention = self.log_file.retention;
+
+		if retention == 0 {
+			fs::remove_file(path)?;
+		} else {
+			// The oldest file is overwritten by the one before it
+			for index in (1..retention).rev() {
+				let rotated = format!("{}.{}", path, index);
+				if fs::metadata(&rotated).is_ok() {
+					fs::rename(&rotated, format!("{}.{}", path, index + 1))?;
+				}
+			}
+			fs::rename(path, format!("{}.1", path))?;
+		}
+		self.open_again()
+	}
+
+	fn open_again(&mut self) -> io::Result<()> {
+		self.file.flush()?;
+		let file = open(&self.log_file.path)?;
+		self.size = file.metadata()?.len();
+		self.file = BufWriter::new(file);
+		self.opened_on = Utc::now().date_naive();
+		Ok(())
+	}
+}
+
+impl Logger for FileLogger {
+	fn write(&mut self, record: &Record) {
+		// Plain lines don't have a timestamp of their own, since the console doesn't need one
+		let line = if self.json {
+			json_logger::format_line(record)
+		} else {
+			format!(
+				"{} {}",
+				record
+					.timestamp
+					.to_rfc3339_opts(SecondsFormat::Millis, true),
+				console_logger::format_line(record)
+			)
+		};
+		if let Err(err) = self.write_line(&line) {
+			eprintln!("Unable to write to log file: {}", err);
+		}
+	}
+
+	fn flush(&mut self) {
+		if let Err(err) = self.file.flush() {
+			eprintln!("Unable to write to log file: {}", err);
+		}
+	}
+
+	fn reopen(&mut self) {
+		if let Err(err) = self.open_again() {
+			eprintln!("Unable to reopen log file: {}", err);
+		}
+	}
+}
+
+fn open(path: &str) -> io::Result<File> {
+	OpenOptions::new().create(true).append(true).open(path)
+}
+
+#[cfg(unix)]
+fn reopen_on_hangup() -> io::Result<()> {
+	use signal_hook::{consts::SIGHUP, iterator::Signals};
+	use std::thread;
+
+	let mut signals = Signals::new([SIGHUP])?;
+	thread::spawn(move || {
+		for _ in signals.forever() {
+			super::reopen();
+		}
+	});
+	Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	use std::path::{Path, PathBuf};
+
+	use chrono::Duration;
+
+	fn create_dir(name: &str) -> PathBuf {
+		let dir = std::env::temp_dir().join(format!("juno-file-logger-{}", name));
+		let _ = fs::remove_dir_all(&dir);
+		fs::create_dir_all(&dir).unwrap();
+		dir
+	}
+
+	// Skips new(), which would listen for SIGHUP on every test
+	fn open_logger(dir: &Path, max_size: u64, daily: bool, retention: usize) -> FileLogger {
+		let path = String::from(dir.join("juno.log").to_str().unwrap());
+		let file = open(&path).unwrap();
+		FileLogger {
+			log_file: LogFile {
+				path,
+				max_size,
+				daily,
+				retention,
+			},
+			json: false,
+			size: file.metadata().unwrap().len(),
+			file: BufWriter::new(file),
+			opened_on: Utc::now().date_naive(),
+		}
+	}
+
+	fn read(dir: &Path, name: &str) -> String {
+		fs::read_to_string(dir.join(name)).unwrap()
+	}
+
+	#[test]
+	fn rotates_at_the_size_limit() {
+		let dir = create_dir("size");
+		let mut logger = open_logger(&dir, 10, false, 3);
+		logger.write_line("first").unwrap();
+		logger.write_line("second").unwrap();
+		logger.write_line("third").unwrap();
+		logger.flush();
+
+		assert_eq!(read(&dir, "juno.log"), "third\n");
+		assert_eq!(read(&dir, "juno.log.1"), "second\n");
+		assert_eq!(read(&dir, "juno.log.2"), "first\n");
+		fs::remove_dir_all(&dir).unwrap();
+	}
+
+	#[test]
+	fn deletes_the_oldest_file_past_retention() {
+		let dir = create_dir("retention");
+		let mut logger = open_logger(&dir, 5, false, 2);
+		for line in ["one", "two", "three", "four"].iter() {
+			logger.write_line(line).unwrap();
+		}
+		logger.flush();
+
+		assert_eq!(read(&dir, "juno.log"), "four\n");
+		assert_eq!(read(&dir, "juno.log.1"), "three\n");
+		assert_eq!(read(&dir, "juno.log.2"), "two\n");
+		assert!(!dir.join("juno.log.3").exists());
+		fs::remove_dir_all(&dir).unwrap();
+	}
+
+	#[test]
+	fn discards_the_file_without_retention() {
+		let dir = create_dir("no-retention");
+		let mut logger = open_logger(&dir, 5, false, 0);
+		logger.write_line("one").unwrap();
+		logger.write_line("two").unwrap();
+		logger.flush();
+
+		assert_eq!(read(&dir, "juno.log"), "two\n");
+		assert!(!dir.join("juno.log.1").exists());
+		fs::remove_dir_all(&dir).unwrap();
+	}
+
+	#[test]
+	fn rotates_when_the_day_changes() {
+		let dir = create_dir("daily");
+		let mut logger = open_logger(&dir, 0, true, 1);
+		logger.write_line("today").unwrap();
+		logger.write_line("still today").unwrap();
+		logger.opened_on -= Duration::days(1);
+		logger.write_line("tomorrow").unwrap();
+		logger.flush();
+
+		assert_eq!(read(&dir, "juno.log"), "tomorrow\n");
+		assert_eq!(read(&dir, "juno.log.1"), "today\nstill today\n");
+		fs::remove_dir_all(&dir).unwrap();
+	}
+
+	#[test]
+	fn keeps_writing_when_the_file_cant_be_rotated() {
+		let dir = create_dir("rotation-failure");
+		// Renaming the file over a directory that isn't empty fails
+		fs::create_dir_all(dir.join("juno.log.1").join("taken")).unwrap();
+		let mut logger = open_logger(&dir, 5, false, 1);
+		logger.write_line("one").unwrap();
+		logger.write_line("two").unwrap();
+		logger.write_line("three").unwrap();
+		logger.flush();
+
+		assert_eq!(read(&dir, "juno.log"), "one\ntwo\nthree\n");
+		fs::remove_dir_all(&dir).unwrap();
+	}
+}
@@ -0,0 +1,200 @@
+use super::{Fields, LogLevel};
+use crate::utils::{constants::log_fields, wildcard};
+
+use std::fmt;
+
+// Decides which logs are written, from a list of comma separated directives:
+//
+// "info,module:billing=verbose,hook:users.*=debug"
+//
+// A bare level (or "juno=<level>") sets the level of every log. "module:<pattern>=<level>" and
+// "hook:<pattern>=<level>" set the level of logs about matching modules and hooks instead, where a
+// '*' in the pattern matches any number of characters. When a log matches more than one
+// directive, the most verbose of them wins
+#[derive(Clone)]
+pub struct Filter {
+	default: LogLevel,
+	directives: Vec<Directive>,
+}
+
+#[derive(Clone)]
+struct Directive {
+	target: Target,
+	pattern: String,
+	level: LogLevel,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Target {
+	Module,
+	Hook,
+}
+
+impl Target {
+	fn get_name(&self) -> &'static str {
+		match self {
+			Target::Module => "module",
+			Target::Hook => "hook",
+		}
+	}
+
+	fn get_field(&self) -> &'static str {
+		match self {
+			Target::Module => log_fields::MODULE_ID,
+			Target::Hook => log_fields::HOOK,
+		}
+	}
+}
+
+impl Filter {
+	pub fn new(default: LogLevel) -> Self {
+		Filter {
+			default,
+			directives: vec![],
+		}
+	}
+
+	// Directives that don't set the level of every log keep the given default
+	pub fn parse(text: &str, default: LogLevel) -> Result<Self, String> {
+		let mut filter = Filter::new(default);
+		for directive in text.split(',').map(str::trim) {
+			if directive.is_empty() {
+				continue;
+			}
+			let (target, level) = match directive.rfind('=') {
+				Some(index) => (&directive[..index], &directive[index + 1..]),
+				None => ("", directive),
+			};
+			let level = LogLevel::parse(level).ok_or_else(|| {
+				format!(
+					"Invalid log filter directive '{}': '{}' is not a log level",
+					directive, level
+				)
+			})?;
+
+			if target.is_empty() || target == "juno" {
+				filter.default = level;
+				continue;
+			}
+			let (target, pattern) = if let Some(pattern) = target.strip_prefix("module:") {
+				(Target::Module, pattern)
+			} else if let Some(pattern) = target.strip_prefix("hook:") {
+				(Target::Hook, pattern)
+			} else {
+				return Err(format!(
+					"Invalid log filter directive '{}': '{}' is not 'juno', 'module:<pattern>' or 'hook:<pattern>'",
+					directive, target
+				));
+			};
+			if pattern.is_empty() {
+				return Err(format!(
+					"Invalid log filter directive '{}': the pattern is empty",
+					directive
+				));
+			}
+			filter.directives.push(Directive {
+				target,
+				pattern: String::from(pattern),
+				level,
+			});
+		}
+		Ok(filter)
+	}
+
+	pub fn get_default(&self) -> LogLevel {
+		self.default
+	}
+
+	// The most verbose level any log can be written at
+	pub fn get_max_level(&self) -> LogLevel {
+		self.directives
+			.iter()
+			.map(|directive| directive.level)
+			.fold(self.default, LogLevel::most_verbose)
+	}
+
+	pub fn allows(&self, log_level: LogLevel, fields: &Fields) -> bool {
+		let matched_level = self
+			.directives
+			.iter()
+			.filter(|directive| {
+				fields.iter().any(|(key, value)| {
+					*key == directive.target.get_field() &&
+						wildcard::matches(&directive.pattern, value)
+				})
+			})
+			.map(|directive| directive.level)
+			.reduce(LogLevel::most_verbose);
+		log_level.is_at_least(matched_level.unwrap_or(self.default))
+	}
+}
+
+impl fmt::Display for Filter {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		write!(f, "{}", self.default.get_name())?;
+		for directive in self.directives.iter() {
+			write!(
+				f,
+				",{}:{}={}",
+				directive.target.get_name(),
+				directive.pattern,
+				directive.level.get_name()
+			)?;
+		}
+		Ok(())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn parses_directives() {
+		let filter = Filter::parse(
+			"debug, module:billing=verbose,hook:users.*=error",
+			LogLevel::Warn,
+		)
+		.unwrap();
+		assert_eq!(
+			filter.to_string(),
+			"debug,module:billing=verbose,hook:users.*=error"
+		);
+		assert!(matches!(filter.get_max_level(), LogLevel::Verbose));
+
+		let filter = Filter::parse("module:billing=info", LogLevel::Warn).unwrap();
+		assert!(matches!(filter.get_default(), LogLevel::Warn));
+		let filter = Filter::parse("juno=error,", LogLevel::Warn).unwrap();
+		assert!(matches!(filter.get_default(), LogLevel::Error));
+	}
+
+	#[test]
+	fn rejects_invalid_directives() {
+		assert!(Filter::parse("loud", LogLevel::Warn).is_err());
+		assert!(Filter::parse("module:billing=loud", LogLevel::Warn).is_err());
+		assert!(Filter::parse("function:billing.*=info", LogLevel::Warn).is_err());
+		assert!(Filter::parse("module:=info", LogLevel::Warn).is_err());
+	}
+
+	#[test]
+	fn allows_logs_by_the_most_verbose_match() {
+		let filter = Filter::parse(
+			"warn,module:billing*=info,hook:billing.*=verbose",
+			LogLevel::Error,
+		)
+		.unwrap();
+
+		assert!(filter.allows(LogLevel::Warn, &Fields::new()));
+		assert!(!filter.allows(LogLevel::Info, &Fields::new()));
+		assert!(!filter.allows(LogLevel::Info, &Fields::new().module_id("users")));
+
+		let billing = Fields::new().module_id("billing-eu");
+		assert!(filter.allows(LogLevel::Info, &billing));
+		assert!(!filter.allows(LogLevel::Debug, &billing));
+
+		let charged = Fields::new()
+			.module_id("billing-eu")
+			.hook("billing.charged");
+		assert!(filter.allows(LogLevel::Verbose, &charged));
+	}
+}
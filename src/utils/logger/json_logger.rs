@@ -0,0 +1,72 @@
+use super::{Logger, Record};
+
+use chrono::SecondsFormat;
+use serde_json::{Map, Value};
+
+// Writes every log as a single line of JSON, for log pipelines to ingest:
+//
+// {"level":"INFO","message":"...","module_id":"...","timestamp":"2020-06-01T12:00:00.000Z"}
+pub struct JsonLogger;
+
+impl JsonLogger {
+	pub fn new() -> Self {
+		JsonLogger
+	}
+}
+
+impl Logger for JsonLogger {
+	fn write(&mut self, record: &Record) {
+		println!("{}", format_line(record));
+	}
+}
+
+pub fn format_line(record: &Record) -> String {
+	let mut line = Map::new();
+	line.insert(
+		String::from("timestamp"),
+		Value::String(
+			record
+				.timestamp
+				.to_rfc3339_opts(SecondsFormat::Millis, true),
+		),
+	);
+	line.insert(
+		String::from("level"),
+		Value::String(String::from(record.log_level.to_string())),
+	);
+	line.insert(String::from("message"), Value::String(record.data.clone()));
+	for (key, value) in record.fields.iter() {
+		line.insert(String::from(*key), Value::String(value.clone()));
+	}
+	Value::Object(line).to_string()
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	use crate::utils::logger::{Fields, LogLevel};
+
+	#[test]
+	fn formats_a_single_object() {
+		let record = Record::new(
+			LogLevel::Warn,
+			Fields::new().module_id("billing").hook("billing.charged"),
+			String::from("Module \"billing\" said:\n{\"not\": \"json\"}"),
+		);
+		let line = format_line(&record);
+		assert!(!line.contains('\n'));
+
+		let value: Value = serde_json::from_str(&line).unwrap();
+		let object = value.as_object().unwrap();
+		assert_eq!(object.len(), 5);
+		assert_eq!(object["level"], "WARN");
+		assert_eq!(
+			object["message"],
+			"Module \"billing\" said:\n{\"not\": \"json\"}"
+		);
+		assert_eq!(object["module_id"], "billing");
+		assert_eq!(object["hook"], "billing.charged");
+		assert!(object["timestamp"].is_string());
+	}
+}
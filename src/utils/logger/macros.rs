@@ -0,0 +1,83 @@
+// Macros for logging formatted messages. The message is only formatted
+// (and the fields are only built) if the log level is enabled
+#![allow(unused_macros)]
+
+macro_rules! log {
+	($log_level:ident, $fields:expr, $($arg:tt)+) => {
+		if $crate::utils::logger::is_enabled($crate::utils::logger::LogLevel::$log_level) {
+			$crate::utils::logger::log(
+				$crate::utils::logger::LogLevel::$log_level,
+				$fields,
+				format!($($arg)+),
+			);
+		}
+	};
+}
+
+macro_rules! verbose {
+	($($arg:tt)+) => {
+		$crate::utils::logger::log!(Verbose, $crate::utils::logger::Fields::new(), $($arg)+)
+	};
+}
+
+macro_rules! info {
+	($($arg:tt)+) => {
+		$crate::utils::logger::log!(Info, $crate::utils::logger::Fields::new(), $($arg)+)
+	};
+}
+
+macro_rules! debug {
+	($($arg:tt)+) => {
+		$crate::utils::logger::log!(Debug, $crate::utils::logger::Fields::new(), $($arg)+)
+	};
+}
+
+// Named differently, since a bare `warn` is also the built-in attribute. It's exported as `warn`
+macro_rules! warn_message {
+	($($arg:tt)+) => {
+		$crate::utils::logger::log!(Warn, $crate::utils::logger::Fields::new(), $($arg)+)
+	};
+}
+
+macro_rules! error {
+	($($arg:tt)+) => {
+		$crate::utils::logger::log!(Error, $crate::utils::logger::Fields::new(), $($arg)+)
+	};
+}
+
+// Along with structured fields about what the log is about
+macro_rules! verbose_with {
+	($fields:expr, $($arg:tt)+) => {
+		$crate::utils::logger::log!(Verbose, $fields, $($arg)+)
+	};
+}
+
+macro_rules! info_with {
+	($fields:expr, $($arg:tt)+) => {
+		$crate::utils::logger::log!(Info, $fields, $($arg)+)
+	};
+}
+
+macro_rules! debug_with {
+	($fields:expr, $($arg:tt)+) => {
+		$crate::utils::logger::log!(Debug, $fields, $($arg)+)
+	};
+}
+
+macro_rules! warn_with {
+	($fields:expr, $($arg:tt)+) => {
+		$crate::utils::logger::log!(Warn, $fields, $($arg)+)
+	};
+}
+
+macro_rules! error_with {
+	($fields:expr, $($arg:tt)+) => {
+		$crate::utils::logger::log!(Error, $fields, $($arg)+)
+	};
+}
+
+#[allow(unused_imports)]
+pub(crate) use {
+	debug, debug_with, error, error_with, info, info_with, log, verbose, verbose_with,
+	warn_message as warn, warn_with,
+};
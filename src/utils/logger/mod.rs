@@ -1,18 +1,75 @@
 pub mod console_logger;
+mod context;
+pub mod file_logger;
+pub mod filter;
+pub mod json_logger;
+mod macros;
+
+use crate::utils::constants::{self, log_fields, log_formats};
 
 use console_logger::ConsoleLogger;
-use std::sync::RwLock;
+use context::WithContext;
+use file_logger::{FileLogger, LogFile};
+use filter::Filter;
+use json_logger::JsonLogger;
+use std::{
+	future::Future,
+	io,
+	sync::{
+		atomic::{AtomicU64, AtomicU8, Ordering},
+		mpsc::{self, SyncSender},
+		RwLock,
+	},
+	thread,
+};
+
+use chrono::{DateTime, Utc};
 
+// From the most verbose to the least
 #[derive(Clone, Copy)]
 pub enum LogLevel {
 	Verbose = 1,
-	Info = 2,
-	Debug = 3,
+	Debug = 2,
+	Info = 3,
 	Warn = 4,
 	Error = 5,
 }
 
 impl LogLevel {
+	pub fn parse(name: &str) -> Option<Self> {
+		match name {
+			"verbose" => Some(LogLevel::Verbose),
+			"debug" => Some(LogLevel::Debug),
+			"info" => Some(LogLevel::Info),
+			"warn" => Some(LogLevel::Warn),
+			"error" => Some(LogLevel::Error),
+			_ => None,
+		}
+	}
+
+	pub fn get_name(&self) -> &'static str {
+		match self {
+			LogLevel::Verbose => "verbose",
+			LogLevel::Debug => "debug",
+			LogLevel::Info => "info",
+			LogLevel::Warn => "warn",
+			LogLevel::Error => "error",
+		}
+	}
+
+	// Whether a log of this level is written when logging at the given level
+	pub fn is_at_least(self, threshold: LogLevel) -> bool {
+		self as u8 >= threshold as u8
+	}
+
+	pub fn most_verbose(self, other: LogLevel) -> LogLevel {
+		if other.is_at_least(self) {
+			self
+		} else {
+			other
+		}
+	}
+
 	pub fn to_string(&self) -> &str {
 		match &self {
 			LogLevel::Verbose => "VERBOSE",
@@ -24,55 +81,247 @@ impl LogLevel {
 	}
 }
 
-trait Logger {
-	fn verbose(&self, data: &str) {
-		self.write(LogLevel::Verbose, data);
+// Structured data about what a log line is about, so that the logs
+// can be searched by module or request without parsing the messages
+#[derive(Clone, Default)]
+pub struct Fields {
+	fields: Vec<(&'static str, String)>,
+}
+
+impl Fields {
+	pub fn new() -> Self {
+		Fields::default()
+	}
+
+	pub fn module_id(self, module_id: &str) -> Self {
+		self.with(log_fields::MODULE_ID, String::from(module_id))
 	}
 
-	fn info(&self, data: &str) {
-		self.write(LogLevel::Info, data);
+	pub fn connection_id(self, connection_id: &u128) -> Self {
+		self.with(log_fields::CONNECTION_ID, connection_id.to_string())
 	}
 
-	fn debug(&self, data: &str) {
-		self.write(LogLevel::Debug, data);
+	pub fn request_id(self, request_id: &str) -> Self {
+		self.with(log_fields::REQUEST_ID, String::from(request_id))
 	}
 
-	fn warn(&self, data: &str) {
-		self.write(LogLevel::Warn, data);
+	pub fn function(self, function: &str) -> Self {
+		self.with(log_fields::FUNCTION, String::from(function))
 	}
 
-	fn error(&self, data: &str) {
-		self.write(LogLevel::Error, data);
+	pub fn hook(self, hook: &str) -> Self {
+		self.with(log_fields::HOOK, String::from(hook))
 	}
 
-	fn write(&self, log_level: LogLevel, data: &str);
+	pub fn is_empty(&self) -> bool {
+		self.fields.is_empty()
+	}
+
+	pub fn iter(&self) -> impl Iterator<Item = &(&'static str, String)> {
+		self.fields.iter()
+	}
+
+	fn with(mut self, key: &'static str, value: String) -> Self {
+		self.fields.push((key, value));
+		self
+	}
+
+	// Adds the fields of the other that this doesn't have already
+	fn merged_with(mut self, other: &Fields) -> Self {
+		for (key, value) in other.iter() {
+			if !self.fields.iter().any(|(existing, _)| existing == key) {
+				self.fields.push((key, value.clone()));
+			}
+		}
+		self
+	}
+}
+
+// A single log, as it's handed over to be written
+pub struct Record {
+	log_level: LogLevel,
+	data: String,
+	fields: Fields,
+	timestamp: DateTime<Utc>,
+}
+
+impl Record {
+	fn new(log_level: LogLevel, fields: Fields, data: String) -> Self {
+		Record {
+			log_level,
+			data,
+			fields,
+			timestamp: Utc::now(),
+		}
+	}
 }
 
+// Loggers only ever run on the writer thread, one record at a time
+trait Logger {
+	fn write(&mut self, record: &Record);
+
+	// Called whenever there's nothing left to write for the moment
+	fn flush(&mut self) {}
+
+	// Called when the output was moved by something else, and has to be opened again
+	fn reopen(&mut self) {}
+}
+
+enum Message {
+	Record(Record),
+	SetLogger(Box<dyn Logger + Send>),
+	Reopen,
+	Flush(mpsc::Sender<()>),
+}
+
+// The most verbose level of the filter, to skip logs without even looking at the filter
+static MAX_LEVEL: AtomicU8 = AtomicU8::new(LogLevel::Verbose as u8);
+// Logs that were dropped since the last one that was written
+static DROPPED: AtomicU64 = AtomicU64::new(0);
+
 lazy_static! {
-	static ref DEFAULT_LOGGER: RwLock<ConsoleLogger> =
-		RwLock::new(ConsoleLogger::new(LogLevel::Verbose));
+	static ref WRITER: SyncSender<Message> = start_writer();
+	static ref FILTER: RwLock<Filter> = RwLock::new(Filter::new(LogLevel::Verbose));
 }
 
+#[allow(unused_imports)]
+pub(crate) use macros::{
+	debug, debug_with, error, error_with, info, info_with, log, verbose, verbose_with, warn,
+	warn_with,
+};
+
 pub fn verbose(data: &str) {
-	DEFAULT_LOGGER.read().unwrap().verbose(data);
+	log_str(LogLevel::Verbose, data);
 }
 
 pub fn info(data: &str) {
-	DEFAULT_LOGGER.read().unwrap().info(data);
+	log_str(LogLevel::Info, data);
 }
 
 pub fn debug(data: &str) {
-	DEFAULT_LOGGER.read().unwrap().debug(data);
+	log_str(LogLevel::Debug, data);
 }
 
 pub fn warn(data: &str) {
-	DEFAULT_LOGGER.read().unwrap().warn(data);
+	log_str(LogLevel::Warn, data);
 }
 
 pub fn error(data: &str) {
-	DEFAULT_LOGGER.read().unwrap().error(data);
+	log_str(LogLevel::Error, data);
+}
+
+// Whether a log of this level could be written at all. Whether it is
+// depends on what it's about, which is only checked once it's logged
+pub fn is_enabled(log_level: LogLevel) -> bool {
+	log_level as u8 >= MAX_LEVEL.load(Ordering::Relaxed)
+}
+
+// Hands the log over to the writer thread. Logging never waits for the log to be
+// written. If the writer has fallen too far behind, the log is dropped instead
+pub fn log(log_level: LogLevel, fields: Fields, data: String) {
+	if !is_enabled(log_level) {
+		return;
+	}
+	let fields = match context::get_current() {
+		Some(context) => fields.merged_with(&context),
+		None => fields,
+	};
+	if !FILTER.read().unwrap().allows(log_level, &fields) {
+		return;
+	}
+	let record = Record::new(log_level, fields, data);
+	if WRITER.try_send(Message::Record(record)).is_err() {
+		DROPPED.fetch_add(1, Ordering::Relaxed);
+	}
+}
+
+pub fn set_filter(filter: Filter) {
+	let mut current = FILTER.write().unwrap();
+	MAX_LEVEL.store(filter.get_max_level() as u8, Ordering::Relaxed);
+	*current = filter;
+}
+
+pub fn get_filter() -> Filter {
+	FILTER.read().unwrap().clone()
+}
+
+// Adds the fields to every log made while the future runs
+pub fn in_context<F: Future>(fields: Fields, future: F) -> WithContext<F> {
+	WithContext::new(fields, future)
 }
 
-pub fn set_verbosity(log_level: LogLevel) {
-	DEFAULT_LOGGER.write().unwrap().set_verbosity(log_level);
+// Switches between the logger implementations. The logs are written
+// to the log file if there is one, and printed otherwise
+pub fn set_output(log_format: &str, log_file: Option<LogFile>) -> io::Result<()> {
+	let json = log_format == log_formats::JSON;
+	let logger: Box<dyn Logger + Send> = match log_file {
+		Some(log_file) => Box::new(FileLogger::new(json, log_file)?),
+		None if json => Box::new(JsonLogger::new()),
+		None => Box::new(ConsoleLogger::new()),
+	};
+	let _ = WRITER.send(Message::SetLogger(logger));
+	Ok(())
+}
+
+// Opens the output again, for when it was moved by something else (like logrotate)
+pub fn reopen() {
+	let _ = WRITER.send(Message::Reopen);
+}
+
+// Waits for every log so far to be written
+pub fn flush() {
+	let (sender, receiver) = mpsc::channel();
+	if WRITER.send(Message::Flush(sender)).is_ok() {
+		let _ = receiver.recv();
+	}
+}
+
+fn log_str(log_level: LogLevel, data: &str) {
+	if is_enabled(log_level) {
+		log(log_level, Fields::new(), String::from(data));
+	}
+}
+
+fn start_writer() -> SyncSender<Message> {
+	let (sender, receiver) = mpsc::sync_channel(constants::LOG_BUFFER_SIZE);
+	thread::spawn(move || {
+		let mut logger: Box<dyn Logger + Send> = Box::new(ConsoleLogger::new());
+		while let Ok(message) = receiver.recv() {
+			handle_message(&mut logger, message);
+			// Everything that's already waiting is written before flushing
+			while let Ok(message) = receiver.try_recv() {
+				handle_message(&mut logger, message);
+			}
+			logger.flush();
+		}
+	});
+	sender
+}
+
+fn handle_message(logger: &mut Box<dyn Logger + Send>, message: Message) {
+	match message {
+		Message::Record(record) => {
+			let dropped = DROPPED.swap(0, Ordering::Relaxed);
+			if dropped > 0 {
+				logger.write(&Record::new(
+					LogLevel::Warn,
+					Fields::new(),
+					format!(
+						"{} logs were dropped because they couldn't be written fast enough",
+						dropped
+					),
+				));
+			}
+			logger.write(&record);
+		}
+		Message::SetLogger(new_logger) => {
+			logger.flush();
+			*logger = new_logger;
+		}
+		Message::Reopen => logger.reopen(),
+		Message::Flush(done) => {
+			logger.flush();
+			let _ = done.send(());
+		}
+	}
 }
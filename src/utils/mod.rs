@@ -1,2 +1,9 @@
+pub mod config;
 pub mod constants;
+pub mod credentials;
+pub mod hook_pattern;
+pub mod json_schema;
 pub mod logger;
+pub mod policy;
+pub mod trace;
+pub mod wildcard;
@@ -0,0 +1,294 @@
+use crate::utils::{constants::rate_limits, wildcard};
+
+use std::fs;
+
+use serde_json::{Map, Value};
+
+// Policy files are JSON objects, declaring which modules can call which functions, listen for
+// which hooks and trigger which hooks. All of them are maps from a function (or hook) pattern to
+// the list of moduleIds that are allowed access. Patterns and moduleIds can contain '*', which
+// matches any number of characters:
+//
+// {
+//     "functions": { "billing.*": ["api-gateway"], "*": ["*"] },
+//     "hooks": { "payments.*": ["billing", "audit-*"] },
+//     "triggers": { "*": ["billing", "payments"] }
+// }
+//
+// When more than one pattern matches, the most specific one (with the most characters that
+// aren't a '*') is used. Anything that doesn't match any pattern is forbidden. If there are no
+// "functions", no "hooks" or no "triggers" at all, they aren't restricted, so that a policy can
+// restrict one of them without having to allow everything else explicitly.
+//
+// The policy can also override the rate limits of modules, by moduleId pattern. Limits that aren't
+// mentioned fall back to the defaults juno was started with, and 0 means there is no limit:
+//
+// {
+//     "rateLimits": { "metrics": { "calls": 1000, "triggers": 100, "bytes": 1048576 } }
+// }
+
+const FUNCTIONS: &str = "functions";
+const HOOKS: &str = "hooks";
+const TRIGGERS: &str = "triggers";
+const RATE_LIMITS: &str = "rateLimits";
+
+pub struct Policy {
+	functions: Option<Vec<Rule>>,
+	hooks: Option<Vec<Rule>>,
+	triggers: Option<Vec<Rule>>,
+	rate_limits: Vec<(String, RateLimits)>,
+}
+
+#[derive(Clone, Default)]
+// How many function calls, hook triggers and bytes a module can send per second
+pub struct RateLimits {
+	pub calls: Option<u64>,
+	pub triggers: Option<u64>,
+	pub bytes: Option<u64>,
+}
+
+impl RateLimits {
+	pub fn get(&self, kind: &str) -> Option<u64> {
+		match kind {
+			rate_limits::CALLS => self.calls,
+			rate_limits::TRIGGERS => self.triggers,
+			rate_limits::BYTES => self.bytes,
+			_ => None,
+		}
+	}
+}
+
+struct Rule {
+	pattern: String,
+	allowed_modules: Vec<String>,
+}
+
+impl Policy {
+	pub fn load(path: &str) -> Result<Self, String> {
+		let contents = fs::read_to_string(path)
+			.map_err(|error| format!("Unable to read policy file '{}': {}", path, error))?;
+		let policy: Value = serde_json::from_str(&contents)
+			.map_err(|error| format!("Policy file '{}' is not valid JSON: {}", path, error))?;
+		let policy = policy
+			.as_object()
+			.ok_or_else(|| format!("Policy file '{}' is not a JSON object", path))?;
+
+		Ok(Policy {
+			functions: get_rules(policy, FUNCTIONS, path)?,
+			hooks: get_rules(policy, HOOKS, path)?,
+			triggers: get_rules(policy, TRIGGERS, path)?,
+			rate_limits: get_rate_limits(policy, path)?,
+		})
+	}
+
+	pub fn can_call(&self, module_id: &str, function: &str) -> bool {
+		match &self.functions {
+			Some(functions) => is_allowed(functions, module_id, function),
+			None => true,
+		}
+	}
+
+	pub fn can_listen(&self, module_id: &str, hook: &str) -> bool {
+		match &self.hooks {
+			Some(hooks) => is_allowed(hooks, module_id, hook),
+			None => true,
+		}
+	}
+
+	// The hook is the fully qualified name of the hook, including the moduleId that triggers it
+	pub fn can_trigger(&self, module_id: &str, hook: &str) -> bool {
+		match &self.triggers {
+			Some(triggers) => is_allowed(triggers, module_id, hook),
+			None => true,
+		}
+	}
+
+	pub fn get_rate_limits(&self, module_id: &str) -> Option<&RateLimits> {
+		self.rate_limits
+			.iter()
+			.filter(|(pattern, _)| wildcard::matches(pattern, module_id))
+			.max_by_key(|(pattern, _)| wildcard::get_specificity(pattern))
+			.map(|(_, rate_limits)| rate_limits)
+	}
+}
+
+fn get_rate_limits(
+	policy: &Map<String, Value>,
+	path: &str,
+) -> Result<Vec<(String, RateLimits)>, String> {
+	let rate_limits = match policy.get(RATE_LIMITS) {
+		None => return Ok(vec![]),
+		Some(rate_limits) => rate_limits.as_object().ok_or_else(|| {
+			format!(
+				"'{}' in policy file '{}' is not an object",
+				RATE_LIMITS, path
+			)
+		})?,
+	};
+
+	let mut result = vec![];
+	for (pattern, limits) in rate_limits {
+		let limits = limits.as_object().ok_or_else(|| {
+			format!(
+				"The rate limits for '{}' in policy file '{}' are not an object",
+				pattern, path
+			)
+		})?;
+		let mut module_limits = RateLimits::default();
+		for (kind, limit) in limits {
+			let limit = limit.as_u64().ok_or_else(|| {
+				format!(
+					"The '{}' rate limit for '{}' in policy file '{}' is not a positive integer",
+					kind, pattern, path
+				)
+			})?;
+			match kind.as_str() {
+				rate_limits::CALLS => module_limits.calls = Some(limit),
+				rate_limits::TRIGGERS => module_limits.triggers = Some(limit),
+				rate_limits::BYTES => module_limits.bytes = Some(limit),
+				_ => {
+					return Err(format!(
+						"Unknown rate limit '{}' for '{}' in policy file '{}'",
+						kind, pattern, path
+					))
+				}
+			}
+		}
+		result.push((pattern.clone(), module_limits));
+	}
+	Ok(result)
+}
+
+fn get_rules(
+	policy: &Map<String, Value>,
+	key: &str,
+	path: &str,
+) -> Result<Option<Vec<Rule>>, String> {
+	let rules = match policy.get(key) {
+		None => return Ok(None),
+		Some(rules) => rules
+			.as_object()
+			.ok_or_else(|| format!("'{}' in policy file '{}' is not an object", key, path))?,
+	};
+
+	let mut result = vec![];
+	for (pattern, allowed_modules) in rules {
+		let allowed_modules = allowed_modules
+			.as_array()
+			.filter(|modules| modules.iter().all(Value::is_string))
+			.ok_or_else(|| {
+				format!(
+					"The modules allowed for '{}' in policy file '{}' are not a list of moduleIds",
+					pattern, path
+				)
+			})?;
+		result.push(Rule {
+			pattern: pattern.clone(),
+			allowed_modules: allowed_modules
+				.iter()
+				.map(|module| String::from(module.as_str().unwrap()))
+				.collect(),
+		});
+	}
+	Ok(Some(result))
+}
+
+fn is_allowed(rules: &[Rule], module_id: &str, name: &str) -> bool {
+	let rule = rules
+		.iter()
+		.filter(|rule| wildcard::matches(&rule.pattern, name))
+		.max_by_key(|rule| wildcard::get_specificity(&rule.pattern));
+
+	match rule {
+		Some(rule) => rule
+			.allowed_modules
+			.iter()
+			.any(|allowed_module| wildcard::matches(allowed_module, module_id)),
+		None => false,
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn load_json(name: &str, contents: &str) -> Result<Policy, String> {
+		let path = std::env::temp_dir().join(format!("juno-policy-{}.json", name));
+		fs::write(&path, contents).unwrap();
+		let policy = Policy::load(path.to_str().unwrap());
+		fs::remove_file(&path).unwrap();
+		policy
+	}
+
+	#[test]
+	fn uses_the_most_specific_rule() {
+		let policy = load_json(
+			"rules",
+			r#"{
+				"functions": { "billing.*": ["api-gateway"], "billing.refund": ["admin-*"], "*": ["*"] },
+				"hooks": { "payments.*": ["billing", "audit-*"] },
+				"triggers": { "*": ["billing", "payments"] }
+			}"#,
+		)
+		.unwrap();
+		assert!(policy.can_call("api-gateway", "billing.charge"));
+		assert!(!policy.can_call("users", "billing.charge"));
+		assert!(policy.can_call("admin-eu", "billing.refund"));
+		assert!(!policy.can_call("api-gateway", "billing.refund"));
+		assert!(policy.can_call("users", "users.get"));
+
+		assert!(policy.can_listen("audit-eu", "payments.refunded"));
+		assert!(!policy.can_listen("users", "payments.refunded"));
+		// Anything that doesn't match a rule is forbidden
+		assert!(!policy.can_listen("billing", "users.created"));
+
+		assert!(policy.can_trigger("payments", "payments.refunded"));
+		assert!(!policy.can_trigger("users", "users.created"));
+	}
+
+	#[test]
+	fn missing_sections_are_unrestricted() {
+		let policy = load_json("functions-only", r#"{ "functions": {} }"#).unwrap();
+		assert!(!policy.can_call("users", "billing.charge"));
+		assert!(policy.can_listen("users", "billing.charged"));
+		assert!(policy.can_trigger("users", "users.created"));
+	}
+
+	#[test]
+	fn reads_rate_limits() {
+		let policy = load_json(
+			"rate-limits",
+			r#"{ "rateLimits": { "*": { "calls": 10 }, "metrics": { "calls": 1000, "bytes": 0 } } }"#,
+		)
+		.unwrap();
+		let limits = policy.get_rate_limits("metrics").unwrap();
+		assert_eq!(limits.get(rate_limits::CALLS), Some(1000));
+		assert_eq!(limits.get(rate_limits::TRIGGERS), None);
+		assert_eq!(limits.get(rate_limits::BYTES), Some(0));
+		assert_eq!(
+			policy
+				.get_rate_limits("users")
+				.unwrap()
+				.get(rate_limits::CALLS),
+			Some(10)
+		);
+		assert!(load_json("no-rate-limits", "{}")
+			.unwrap()
+			.get_rate_limits("users")
+			.is_none());
+	}
+
+	#[test]
+	fn rejects_invalid_files() {
+		assert!(load_json("not-json", "functions").is_err());
+		assert!(load_json("not-an-object", "[]").is_err());
+		assert!(load_json("bad-section", r#"{ "hooks": [] }"#).is_err());
+		assert!(load_json("bad-modules", r#"{ "triggers": { "*": "billing" } }"#).is_err());
+		assert!(load_json("bad-limit", r#"{ "rateLimits": { "*": { "calls": -1 } } }"#).is_err());
+		assert!(load_json(
+			"unknown-limit",
+			r#"{ "rateLimits": { "*": { "hooks": 1 } } }"#
+		)
+		.is_err());
+	}
+}
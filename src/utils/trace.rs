@@ -0,0 +1,209 @@
+use crate::utils::logger;
+
+use std::time::{Duration, Instant};
+
+use rand::{thread_rng, Rng};
+
+// Trace contexts follow the W3C traceparent format: "00-<trace-id>-<parent-id>-<flags>", where the
+// trace-id is 32 hex characters shared by every hop of a request, the parent-id is 16 hex characters
+// identifying the span of the hop that sent it, and the flags are 2 hex characters:
+//
+// "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01"
+
+const VERSION: &str = "00";
+const SAMPLED: u8 = 1;
+
+#[derive(Clone)]
+pub struct TraceContext {
+	trace_id: String,
+	span_id: String,
+	flags: u8,
+}
+
+impl TraceContext {
+	// Starts a new trace, for requests that aren't part of one yet
+	pub fn new_root() -> Self {
+		TraceContext {
+			trace_id: format!("{:032x}", generate_id::<u128>()),
+			span_id: format!("{:016x}", generate_id::<u64>()),
+			flags: SAMPLED,
+		}
+	}
+
+	pub fn parse(traceparent: &str) -> Option<Self> {
+		let parts: Vec<&str> = traceparent.split('-').collect();
+		if parts.len() != 4 ||
+			!is_hex(parts[0], 2) ||
+			parts[0] == "ff" ||
+			!is_hex(parts[1], 32) ||
+			!is_hex(parts[2], 16) ||
+			!is_hex(parts[3], 2)
+		{
+			return None;
+		}
+		// All zeroes are not valid IDs
+		if parts[1].chars().all(|c| c == '0') || parts[2].chars().all(|c| c == '0') {
+			return None;
+		}
+		Some(TraceContext {
+			trace_id: String::from(parts[1]),
+			span_id: String::from(parts[2]),
+			flags: u8::from_str_radix(parts[3], 16).unwrap(),
+		})
+	}
+
+	// Creates the context of the next hop of the same trace
+	pub fn new_child(&self) -> Self {
+		TraceContext {
+			trace_id: self.trace_id.clone(),
+			span_id: format!("{:016x}", generate_id::<u64>()),
+			flags: self.flags,
+		}
+	}
+
+	// Exposing trace_id
+	pub fn get_trace_id(&self) -> &String {
+		&self.trace_id
+	}
+
+	// Exposing span_id
+	pub fn get_span_id(&self) -> &String {
+		&self.span_id
+	}
+
+	pub fn to_traceparent(&self) -> String {
+		format!(
+			"{}-{}-{}-{:02x}",
+			VERSION, self.trace_id, self.span_id, self.flags
+		)
+	}
+}
+
+#[derive(Clone)]
+// A single hop of a request through juno. Its start and finish are logged along with the trace,
+// so that the whole tree of a request can be put back together from the logs
+pub struct Span {
+	context: TraceContext,
+	parent: TraceContext,
+	name: String,
+	started: Instant,
+}
+
+impl Span {
+	pub fn start(parent: &TraceContext, name: String) -> Self {
+		let span = Span {
+			context: parent.new_child(),
+			parent: parent.clone(),
+			name,
+			started: Instant::now(),
+		};
+		logger::info!(
+			"[trace {}] Span {} (parent {}) started: {}",
+			span.context.get_trace_id(),
+			span.context.get_span_id(),
+			span.parent.get_span_id(),
+			span.name
+		);
+		span
+	}
+
+	// Exposing context
+	pub fn get_context(&self) -> &TraceContext {
+		&self.context
+	}
+
+	// Exposing parent
+	pub fn get_parent(&self) -> &TraceContext {
+		&self.parent
+	}
+
+	pub fn get_elapsed(&self) -> Duration {
+		self.started.elapsed()
+	}
+
+	pub fn finish(&self, outcome: &str) {
+		logger::info!(
+			"[trace {}] Span {} (parent {}) finished in {:.3}ms, {}: {}",
+			self.context.get_trace_id(),
+			self.context.get_span_id(),
+			self.parent.get_span_id(),
+			self.started.elapsed().as_secs_f64() * 1000.0,
+			outcome,
+			self.name
+		);
+	}
+}
+
+fn is_hex(text: &str, length: usize) -> bool {
+	text.len() == length &&
+		text.chars()
+			.all(|c| c.is_ascii_digit() || ('a'..='f').contains(&c))
+}
+
+fn generate_id<T>() -> T
+where
+	T: Default + PartialEq,
+	rand::distributions::Standard: rand::distributions::Distribution<T>,
+{
+	let mut id = thread_rng().gen();
+	while id == T::default() {
+		id = thread_rng().gen();
+	}
+	id
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	const TRACEPARENT: &str = "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01";
+
+	#[test]
+	fn parses_traceparents() {
+		let trace = TraceContext::parse(TRACEPARENT).unwrap();
+		assert_eq!(trace.get_trace_id(), "4bf92f3577b34da6a3ce929d0e0e4736");
+		assert_eq!(trace.get_span_id(), "00f067aa0ba902b7");
+		assert_eq!(trace.to_traceparent(), TRACEPARENT);
+
+		// Unknown flags are kept, to be passed on as they are
+		let trace = TraceContext::parse("00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-03");
+		assert!(trace.unwrap().to_traceparent().ends_with("-03"));
+	}
+
+	#[test]
+	fn rejects_invalid_traceparents() {
+		for traceparent in [
+			"",
+			"00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7",
+			"00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01-00",
+			"ff-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01",
+			"00-4BF92F3577B34DA6A3CE929D0E0E4736-00f067aa0ba902b7-01",
+			"00-4bf92f3577b34da6a3ce929d0e0e473-00f067aa0ba902b7-01",
+			"00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b-01",
+			"00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-1",
+			"00-00000000000000000000000000000000-00f067aa0ba902b7-01",
+			"00-4bf92f3577b34da6a3ce929d0e0e4736-0000000000000000-01",
+			"00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902bg-01",
+		]
+		.iter()
+		{
+			assert!(
+				TraceContext::parse(traceparent).is_none(),
+				"{} should be invalid",
+				traceparent
+			);
+		}
+	}
+
+	#[test]
+	fn children_share_the_trace() {
+		let trace = TraceContext::parse(TRACEPARENT).unwrap();
+		let span = Span::start(&trace, String::from("test"));
+		assert_eq!(span.get_context().get_trace_id(), trace.get_trace_id());
+		assert_ne!(span.get_context().get_span_id(), trace.get_span_id());
+		assert_eq!(span.get_parent().get_span_id(), trace.get_span_id());
+
+		let root = TraceContext::new_root();
+		assert!(TraceContext::parse(&root.to_traceparent()).is_some());
+	}
+}
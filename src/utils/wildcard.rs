@@ -0,0 +1,61 @@
+// Matches text against patterns where a '*' matches any number of characters, including none
+
+pub fn matches(pattern: &str, text: &str) -> bool {
+	let pattern: Vec<char> = pattern.chars().collect();
+	let text: Vec<char> = text.chars().collect();
+
+	// Position of the last '*' in the pattern, and where in the text it started matching
+	let mut backtrack: Option<(usize, usize)> = None;
+	let (mut p, mut t) = (0, 0);
+	while t < text.len() {
+		if p < pattern.len() && pattern[p] == '*' {
+			backtrack = Some((p, t));
+			p += 1;
+		} else if p < pattern.len() && pattern[p] == text[t] {
+			p += 1;
+			t += 1;
+		} else if let Some((star, start)) = backtrack {
+			// Let the last '*' match one more character
+			p = star + 1;
+			t = start + 1;
+			backtrack = Some((star, start + 1));
+		} else {
+			return false;
+		}
+	}
+	pattern[p..].iter().all(|c| *c == '*')
+}
+
+// How specific a pattern is. When more than one pattern matches, the most specific one should win
+pub fn get_specificity(pattern: &str) -> usize {
+	pattern.chars().filter(|c| *c != '*').count()
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn matches_patterns() {
+		assert!(matches("billing", "billing"));
+		assert!(matches("*", ""));
+		assert!(matches("*", "billing"));
+		assert!(matches("billing-*", "billing-"));
+		assert!(matches("billing-*", "billing-eu"));
+		assert!(matches("*-eu", "billing-eu"));
+		assert!(matches("b*g-*u", "billing-eu"));
+		assert!(matches("*a*a*", "banana"));
+
+		assert!(!matches("billing", "billing-eu"));
+		assert!(!matches("billing-*", "billing"));
+		assert!(!matches("*-eu", "billing-us"));
+		assert!(!matches("*a*a*a*a", "banana"));
+	}
+
+	#[test]
+	fn counts_specificity() {
+		assert_eq!(get_specificity("*"), 0);
+		assert_eq!(get_specificity("billing-*"), 8);
+		assert_eq!(get_specificity("billing-eu"), 10);
+	}
+}